@@ -0,0 +1,115 @@
+/*!
+Percent-decoded userinfo taken from a BaseUrl, and the HTTP Basic authentication header built from it.
+
+The username and password held by a BaseUrl are stored percent-encoded, as they appear in the
+serialization. The Credentials type holds the decoded values, ready to be used outside of the url.
+ */
+
+use url::percent_encoding::percent_decode;
+
+use std::fmt::{ Debug, Formatter, Result as FormatResult };
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A decoded username and optional password. The Debug output never shows the password.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Credentials {
+    /// The decoded username, this may be empty if only a password was present
+    pub username: String,
+    /// The decoded password, if one was present
+    pub password: Option< String >,
+}
+
+impl Credentials {
+
+    /// Create a new set of credentials from already decoded values
+    pub fn new( username:&str, password:Option< &str > ) -> Self {
+        Credentials{
+            username: username.to_owned( ),
+            password: password.map( |p| p.to_owned( ) ),
+        }
+    }
+
+    /// Decode a percent-encoded username and password as returned by `BaseUrl::username( )` and
+    /// `BaseUrl::password( )`. Invalid UTF-8 sequences are replaced with U+FFFD, use
+    /// `BaseUrl::basic_auth_header( )` for a header carrying the original bytes.
+    pub fn from_encoded( username:&str, password:Option< &str > ) -> Self {
+        Credentials{
+            username: percent_decode( username.as_bytes( ) ).decode_utf8_lossy( ).into_owned( ),
+            password: password.map( |p| percent_decode( p.as_bytes( ) ).decode_utf8_lossy( ).into_owned( ) ),
+        }
+    }
+
+    /// Return the value of an `Authorization` header using the Basic scheme described in RFC 7617.
+    ///
+    /// A missing password is treated as an empty one. Returns None if the username contains a `:`,
+    /// which the Basic scheme cannot represent.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::Credentials;
+    ///
+    /// let credentials = Credentials::new( "Aladdin", Some( "open sesame" ) );
+    /// assert_eq!( credentials.basic_auth_header( ), Some( "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==".to_owned( ) ) );
+    ///
+    /// assert_eq!( Credentials::new( "Ala:ddin", Some( "open sesame" ) ).basic_auth_header( ), None );
+    /// ```
+    pub fn basic_auth_header( &self ) -> Option< String > {
+        basic_auth_header( self.username.as_bytes( ), self.password.as_ref( ).map( |p| p.as_bytes( ) ) )
+    }
+}
+
+/// Return the value of a Basic `Authorization` header for a percent-encoded username and password.
+/// The decoded bytes are used as they are, so userinfo which is not valid UTF-8 is sent unchanged
+/// rather than with U+FFFD in place of each invalid sequence.
+pub(crate) fn encoded_basic_auth_header( username:&str, password:Option< &str > ) -> Option< String > {
+    let username:Vec< u8 > = percent_decode( username.as_bytes( ) ).collect( );
+    let password:Option< Vec< u8 > > = password.map( |p| percent_decode( p.as_bytes( ) ).collect( ) );
+    basic_auth_header( &username, password.as_deref( ) )
+}
+
+fn basic_auth_header( username:&[u8], password:Option< &[u8] > ) -> Option< String > {
+    if username.contains( &b':' ) {
+        return None;
+    }
+    let mut user_pass = username.to_vec( );
+    user_pass.push( b':' );
+    user_pass.extend_from_slice( password.unwrap_or( &[] ) );
+    Some( format!( "Basic {}", base64_encode( &user_pass ) ) )
+}
+
+/// The password is replaced with `[redacted]`, so credentials can't leak into logs or panic messages
+///
+/// # Examples
+///
+/// ```rust
+/// use base_url::Credentials;
+///
+/// let credentials = Credentials::new( "brady", Some( "hunter3" ) );
+/// assert_eq!( format!( "{:?}", credentials ), r#"Credentials { username: "brady", password: Some("[redacted]") }"# );
+/// ```
+impl Debug for Credentials {
+    fn fmt( &self, formatter:&mut Formatter ) -> FormatResult {
+        formatter.debug_struct( "Credentials" )
+                 .field( "username", &self.username )
+                 .field( "password", &self.password.as_ref( ).map( |_| "[redacted]" ) )
+                 .finish( )
+    }
+}
+
+fn base64_encode( input:&[u8] ) -> String {
+    let mut out = String::with_capacity( input.len( ).div_ceil( 3 ) * 4 );
+    for chunk in input.chunks( 3 ) {
+        let b = [ chunk[0], *chunk.get( 1 ).unwrap_or( &0 ), *chunk.get( 2 ).unwrap_or( &0 ) ];
+        let n = ( u32::from( b[0] ) << 16 ) | ( u32::from( b[1] ) << 8 ) | u32::from( b[2] );
+        for i in 0..4 {
+            if i <= chunk.len( ) {
+                out.push( BASE64_ALPHABET[ ( ( n >> ( 18 - 6 * i ) ) & 0x3f ) as usize ] as char );
+            } else {
+                out.push( '=' );
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,493 @@
+/*!
+Extracting the links of an HTML document, resolved against the document's BaseUrl.
+
+Links are streamed from the document's bytes one at a time and are taken from every `href`, `src`,
+`srcset`, `action` and `formaction` attribute, the target of a `<meta http-equiv="refresh">` element,
+and every CSS `url( )` in `<style>` elements and `style` attributes. Each is tagged with the element
+and kind of attribute it came from.
+
+As HTML specifies, the first `<base>` element with an `href` attribute sets the Url every link in the
+document is resolved against, wherever in the document it appears. Links which cannot be resolved, and
+`javascript:` and `data:` Urls, are skipped.
+
+The document is tokenized leniently the way a browser would: comments, the contents of `<script>`
+elements and other raw text are skipped, and attribute values may be unquoted. Character references
+are decoded in attribute values, though only numeric references and the named references `&amp;`,
+`&lt;`, `&gt;`, `&quot;`, `&apos;` and `&nbsp;` are recognised. The bytes are read as UTF-8.
+
+```
+use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+use base_url::extract::{ LinkExtractor, LinkKind };
+
+# fn run( ) -> Result< (), BaseUrlError > {
+let document = BaseUrl::try_from( "https://example.org/blog/post.html" )?;
+let html = br#"<!DOCTYPE html>
+<html><head>
+  <link rel=stylesheet href="../css/site.css">
+  <base href="https://cdn.example.net/assets/">
+  <style>body { background: url( 'img/bg.png' ) }</style>
+</head><body>
+  <a href="/about?a=1&amp;b=2">About</a>
+  <a href="javascript:void(0)">Nothing</a>
+  <img src="logo.png" srcset="logo-2x.png 2x, logo-3x.png 3x">
+  <script>var a = '<a href="hidden">';</script>
+</body></html>"#;
+
+let links:Vec< _ > = LinkExtractor::new( html, &document ).map( |link| ( link.element, link.kind, link.url.into_string( ) ) ).collect( );
+assert_eq!( links, vec![
+    ( "link".to_owned( ), LinkKind::Href, "https://cdn.example.net/css/site.css".to_owned( ) ),
+    ( "style".to_owned( ), LinkKind::Style, "https://cdn.example.net/assets/img/bg.png".to_owned( ) ),
+    ( "a".to_owned( ), LinkKind::Href, "https://cdn.example.net/about?a=1&b=2".to_owned( ) ),
+    ( "img".to_owned( ), LinkKind::Src, "https://cdn.example.net/assets/logo.png".to_owned( ) ),
+    ( "img".to_owned( ), LinkKind::Srcset, "https://cdn.example.net/assets/logo-2x.png".to_owned( ) ),
+    ( "img".to_owned( ), LinkKind::Srcset, "https://cdn.example.net/assets/logo-3x.png".to_owned( ) ),
+] );
+# Ok( () )
+# }
+# run( );
+```
+ */
+
+use crate::{ BaseUrl, Url };
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+/// The kind of markup a link was found in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    /// An `href` attribute
+    Href,
+    /// A `src` attribute
+    Src,
+    /// A candidate of a `srcset` attribute
+    Srcset,
+    /// An `action` or `formaction` attribute
+    Action,
+    /// The target of a `<meta http-equiv="refresh">` element
+    Refresh,
+    /// A CSS `url( )` in a `<style>` element or a `style` attribute
+    Style,
+}
+
+/// A link found in a document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtractedLink {
+    /// The link, resolved against the document's base Url
+    pub url: Url,
+    /// The lowercased name of the element the link was found in
+    pub element: String,
+    /// The kind of markup the link was found in
+    pub kind: LinkKind,
+}
+
+/// A streaming iterator over the links of an HTML document
+pub struct LinkExtractor<'a> {
+    tokenizer: Tokenizer<'a>,
+    base: Url,
+    pending: VecDeque< ExtractedLink >,
+}
+
+impl<'a> LinkExtractor<'a> {
+
+    /// Begin extracting the links of an HTML document retrieved from the given BaseUrl. The document is
+    /// scanned for a `<base href>` element before any link is yielded.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::extract::{ LinkExtractor, LinkKind };
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let document = BaseUrl::try_from( "http://example.org/shop/" )?;
+    /// let html = b"<!-- <a href=commented> -->\n\
+    ///              <meta http-equiv=Refresh content=\"5; URL='cart?from=meta'\">\n\
+    ///              <form action=search><button formaction=\"/search/advanced\"></button></form>\n\
+    ///              <div style=\"/*/ url(hidden) */ background:url(&quot;tile.png&quot;)\"></div>\n\
+    ///              <iframe src=\"data:text/html,hello\"></iframe>\n\
+    ///              <base target=_blank><base href=\"/store/\"><base href=\"//ignored.example.org/\">";
+    ///
+    /// let extractor = LinkExtractor::new( html, &document );
+    /// assert_eq!( extractor.base( ).as_str( ), "http://example.org/store/" );
+    ///
+    /// let links:Vec< _ > = extractor.map( |link| ( link.kind, link.url.into_string( ) ) ).collect( );
+    /// assert_eq!( links, vec![
+    ///     ( LinkKind::Refresh, "http://example.org/store/cart?from=meta".to_owned( ) ),
+    ///     ( LinkKind::Action, "http://example.org/store/search".to_owned( ) ),
+    ///     ( LinkKind::Action, "http://example.org/search/advanced".to_owned( ) ),
+    ///     ( LinkKind::Style, "http://example.org/store/tile.png".to_owned( ) ),
+    /// ] );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn new( html:&'a [u8], document:&BaseUrl ) -> Self {
+        let document:&Url = document.as_ref( );
+        let mut scan = Tokenizer{ html, pos: 0 };
+        let base = loop {
+            match scan.next_tag( ) {
+                Some( tag ) if tag.name == "base" => {
+                    if let Some( href ) = tag.attr( "href" ) {
+                        break document.join( href.trim( ) ).unwrap_or_else( |_| document.clone( ) );
+                    }
+                }
+                Some( _ ) => {}
+                None => break document.clone( ),
+            }
+        };
+        LinkExtractor{ tokenizer: Tokenizer{ html, pos: 0 }, base, pending: VecDeque::new( ) }
+    }
+
+    /// The Url links are resolved against: the document's BaseUrl, or the target of its first
+    /// `<base href>` element
+    pub fn base( &self ) -> &Url {
+        &self.base
+    }
+
+    fn push( &mut self, element:&str, kind:LinkKind, link:&str ) {
+        let url = match self.base.join( link.trim( ) ) {
+            Ok( url ) => url,
+            Err( _ ) => return,
+        };
+        if url.scheme( ) == "javascript" || url.scheme( ) == "data" {
+            return;
+        }
+        self.pending.push_back( ExtractedLink{ url, element: element.to_owned( ), kind } );
+    }
+
+    fn push_css( &mut self, element:&str, css:&str ) {
+        for link in css_urls( css ) {
+            self.push( element, LinkKind::Style, &link );
+        }
+    }
+
+    /// Queue the links of a single tag
+    fn read_tag( &mut self, tag:Tag ) {
+        let element = tag.name.as_str( );
+        if element == "base" {
+            return;
+        }
+        for ( name, value ) in &tag.attrs {
+            match name.as_str( ) {
+                "href" => self.push( element, LinkKind::Href, value ),
+                "src" => self.push( element, LinkKind::Src, value ),
+                "srcset" => for candidate in srcset_urls( value ) {
+                    self.push( element, LinkKind::Srcset, candidate );
+                },
+                "action" | "formaction" => self.push( element, LinkKind::Action, value ),
+                "style" => self.push_css( element, value ),
+                _ => {}
+            }
+        }
+        if element == "meta" && tag.attr( "http-equiv" ).is_some_and( |v| v.trim( ).eq_ignore_ascii_case( "refresh" ) ) {
+            if let Some( target ) = tag.attr( "content" ).and_then( refresh_url ) {
+                self.push( element, LinkKind::Refresh, target );
+            }
+        }
+        if element == "style" {
+            if let Some( text ) = tag.text {
+                self.push_css( element, &String::from_utf8_lossy( text ) );
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for LinkExtractor<'a> {
+    type Item = ExtractedLink;
+
+    fn next( &mut self ) -> Option< Self::Item > {
+        loop {
+            if let Some( link ) = self.pending.pop_front( ) {
+                return Some( link );
+            }
+            let tag = self.tokenizer.next_tag( )?;
+            self.read_tag( tag );
+        }
+    }
+}
+
+/// A start tag
+struct Tag<'a> {
+    /// The lowercased tag name
+    name: String,
+    /// The lowercased attribute names and decoded values, without duplicates
+    attrs: Vec< ( String, String ) >,
+    /// The contents of a raw text element such as `<style>`
+    text: Option< &'a [u8] >,
+}
+
+impl<'a> Tag<'a> {
+
+    fn attr( &self, name:&str ) -> Option< &str > {
+        self.attrs.iter( ).find( |( n, _ )| n == name ).map( |( _, v )| v.as_str( ) )
+    }
+}
+
+/// Elements whose contents are text rather than markup
+const RAW_TEXT:&[&str] = &[ "script", "style", "textarea", "title", "xmp", "iframe", "noembed", "noframes" ];
+
+struct Tokenizer<'a> {
+    html: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+
+    fn peek( &self ) -> Option< u8 > {
+        self.html.get( self.pos ).cloned( )
+    }
+
+    fn skip_while< F:Fn( u8 ) -> bool >( &mut self, f:F ) -> &'a [u8] {
+        let start = self.pos;
+        while self.peek( ).is_some_and( &f ) {
+            self.pos += 1;
+        }
+        &self.html[start..self.pos]
+    }
+
+    /// Move past the next occurrence of `pattern`, or to the end of the document
+    fn skip_past( &mut self, pattern:&[u8] ) {
+        self.pos = match find( &self.html[self.pos..], pattern ) {
+            Some( i ) => self.pos + i + pattern.len( ),
+            None => self.html.len( ),
+        };
+    }
+
+    /// Read up to and including the next start tag
+    fn next_tag( &mut self ) -> Option< Tag<'a> > {
+        loop {
+            self.skip_while( |b| b != b'<' );
+            self.peek( )?;
+            let rest = &self.html[self.pos..];
+            if rest.starts_with( b"<!--" ) {
+                self.pos += 4;
+                self.skip_past( b"-->" );
+            } else if rest.starts_with( b"<!" ) || rest.starts_with( b"<?" ) || rest.starts_with( b"</" ) {
+                self.skip_past( b">" );
+            } else if rest.get( 1 ).is_some_and( |b| b.is_ascii_alphabetic( ) ) {
+                self.pos += 1;
+                return Some( self.read_start_tag( ) );
+            } else {
+                self.pos += 1;
+            }
+        }
+    }
+
+    fn read_start_tag( &mut self ) -> Tag<'a> {
+        let name = self.skip_while( |b| !is_space( b ) && b != b'/' && b != b'>' );
+        let name = String::from_utf8_lossy( name ).to_ascii_lowercase( );
+        let mut attrs:Vec< ( String, String ) > = Vec::new( );
+
+        loop {
+            self.skip_while( |b| is_space( b ) || b == b'/' );
+            match self.peek( ) {
+                None => break,
+                Some( b'>' ) => {
+                    self.pos += 1;
+                    break;
+                }
+                Some( _ ) => {}
+            }
+            let start = self.pos;
+            self.pos += 1;
+            self.skip_while( |b| !is_space( b ) && b != b'/' && b != b'>' && b != b'=' );
+            let attr = String::from_utf8_lossy( &self.html[start..self.pos] ).to_ascii_lowercase( );
+            self.skip_while( is_space );
+
+            let mut value = Cow::Borrowed( "" );
+            if self.peek( ) == Some( b'=' ) {
+                self.pos += 1;
+                self.skip_while( is_space );
+                let raw = match self.peek( ) {
+                    Some( quote ) if quote == b'"' || quote == b'\'' => {
+                        self.pos += 1;
+                        let raw = self.skip_while( |b| b != quote );
+                        self.pos = ( self.pos + 1 ).min( self.html.len( ) );
+                        raw
+                    }
+                    _ => self.skip_while( |b| !is_space( b ) && b != b'>' ),
+                };
+                value = String::from_utf8_lossy( raw );
+            }
+            if !attrs.iter( ).any( |( n, _ )| *n == attr ) {
+                attrs.push( ( attr, decode_references( &value ) ) );
+            }
+        }
+
+        let mut text = None;
+        if RAW_TEXT.contains( &name.as_str( ) ) {
+            let start = self.pos;
+            let end = self.find_end_tag( &name );
+            text = Some( &self.html[start..end] );
+            self.pos = end;
+        } else if name == "plaintext" {
+            self.pos = self.html.len( );
+        }
+        Tag{ name, attrs, text }
+    }
+
+    /// The position of the end tag closing a raw text element, or the end of the document
+    fn find_end_tag( &self, name:&str ) -> usize {
+        let mut pos = self.pos;
+        while let Some( i ) = find( &self.html[pos..], b"</" ) {
+            let start = pos + i;
+            let after = start + 2 + name.len( );
+            let matches = self.html.get( start + 2..after ).is_some_and( |n| n.eq_ignore_ascii_case( name.as_bytes( ) ) )
+                && self.html.get( after ).is_none_or( |&b| is_space( b ) || b == b'/' || b == b'>' );
+            if matches {
+                return start;
+            }
+            pos = start + 2;
+        }
+        self.html.len( )
+    }
+}
+
+fn is_space( b:u8 ) -> bool {
+    b == b' ' || b == b'\t' || b == b'\n' || b == b'\r' || b == b'\x0c'
+}
+
+fn find( haystack:&[u8], needle:&[u8] ) -> Option< usize > {
+    haystack.windows( needle.len( ) ).position( |w| w == needle )
+}
+
+/// Decode the character references in an attribute value
+fn decode_references( value:&str ) -> String {
+    let mut decoded = String::with_capacity( value.len( ) );
+    let mut rest = value;
+    while let Some( i ) = rest.find( '&' ) {
+        decoded.push_str( &rest[..i] );
+        rest = &rest[i..];
+        let ( c, len ) = decode_reference( rest ).unwrap_or( ( '&', 1 ) );
+        decoded.push( c );
+        rest = &rest[len..];
+    }
+    decoded.push_str( rest );
+    decoded
+}
+
+/// Decode the character reference at the start of the input, returning the character and the length
+/// of the reference
+fn decode_reference( input:&str ) -> Option< ( char, usize ) > {
+    let end = input.find( ';' )?;
+    let name = &input[1..end];
+    let c = if let Some( number ) = name.strip_prefix( '#' ) {
+        let code = match number.strip_prefix( [ 'x', 'X' ] ) {
+            Some( hex ) => u32::from_str_radix( hex, 16 ).ok( )?,
+            None => number.parse( ).ok( )?,
+        };
+        char::from_u32( code ).filter( |&c| c != '\0' ).unwrap_or( '\u{FFFD}' )
+    } else {
+        match name {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            "nbsp" => '\u{A0}',
+            _ => return None,
+        }
+    };
+    Some( ( c, end + 1 ) )
+}
+
+/// The Urls of the candidates of a `srcset` attribute, skipping their descriptors
+fn srcset_urls( value:&str ) -> Vec< &str > {
+    let mut urls = Vec::new( );
+    let mut rest = value;
+    loop {
+        rest = rest.trim_start_matches( |c:char| c.is_ascii_whitespace( ) || c == ',' );
+        if rest.is_empty( ) {
+            return urls;
+        }
+        let end = rest.find( |c:char| c.is_ascii_whitespace( ) ).unwrap_or( rest.len( ) );
+        let url = &rest[..end];
+        rest = &rest[end..];
+        let trimmed = url.trim_end_matches( ',' );
+        if trimmed.len( ) == url.len( ) {
+            // Skip the descriptors, which run to the next comma outside of parentheses
+            let mut depth = 0usize;
+            let end = rest.char_indices( )
+                          .find( |&( _, c )| match c {
+                              '(' => { depth += 1; false }
+                              ')' => { depth = depth.saturating_sub( 1 ); false }
+                              ',' => depth == 0,
+                              _ => false,
+                          } )
+                          .map_or( rest.len( ), |( i, _ )| i );
+            rest = &rest[end..];
+        }
+        urls.push( trimmed );
+    }
+}
+
+/// The Url of a `<meta http-equiv="refresh">` element's content, as HTML's shared declarative refresh
+/// steps find it
+fn refresh_url( content:&str ) -> Option< &str > {
+    let rest = content.trim_start( ).trim_start_matches( |c:char| c.is_ascii_digit( ) || c == '.' );
+    let rest = rest.trim_start( );
+    let rest = rest.strip_prefix( [ ';', ',' ] ).unwrap_or( rest ).trim_start( );
+    let rest = match rest.get( ..3 ) {
+        Some( u ) if u.eq_ignore_ascii_case( "url" ) => {
+            let after = rest[3..].trim_start( );
+            match after.strip_prefix( '=' ) {
+                Some( after ) => after.trim_start( ),
+                None => rest,
+            }
+        }
+        _ => rest,
+    };
+    let url = match rest.chars( ).next( ) {
+        Some( quote ) if quote == '"' || quote == '\'' => {
+            let rest = &rest[1..];
+            &rest[..rest.find( quote ).unwrap_or( rest.len( ) )]
+        }
+        _ => rest.trim_end( ),
+    };
+    if url.is_empty( ) { None } else { Some( url ) }
+}
+
+/// The arguments of every `url( )` in a stylesheet, skipping comments
+fn css_urls( css:&str ) -> Vec< String > {
+    let mut urls = Vec::new( );
+    let mut rest = css;
+    while let Some( i ) = rest.find( [ '/', 'u', 'U' ] ) {
+        rest = &rest[i..];
+        if rest.starts_with( "/*" ) {
+            // The comment cannot end with the '*' that opened it, as in `/*/`
+            rest = rest[2..].find( "*/" ).map_or( "", |end| &rest[end + 4..] );
+            continue;
+        }
+        if !rest.get( ..4 ).is_some_and( |f| f.eq_ignore_ascii_case( "url(" ) ) {
+            rest = &rest[1..];
+            continue;
+        }
+        let args = rest[4..].trim_start( );
+        let mut url = String::new( );
+        let mut chars = args.char_indices( );
+        let end = match args.chars( ).next( ) {
+            Some( quote ) if quote == '"' || quote == '\'' => {
+                chars.next( );
+                loop {
+                    match chars.next( ) {
+                        Some( ( i, c ) ) if c == quote => break i + 1,
+                        Some( ( _, '\\' ) ) => url.extend( chars.next( ).map( |( _, c )| c ) ),
+                        Some( ( _, c ) ) => url.push( c ),
+                        None => break args.len( ),
+                    }
+                }
+            }
+            _ => {
+                let end = args.find( ')' ).unwrap_or( args.len( ) );
+                url.push_str( args[..end].trim_end( ) );
+                end
+            }
+        };
+        rest = &args[end..];
+        if !url.is_empty( ) {
+            urls.push( url );
+        }
+    }
+    urls
+}
@@ -0,0 +1,87 @@
+/*!
+Options for BaseUrl::parse_lenient, which accepts addresses as people type them, and the fixups it
+reports applying.
+ */
+
+use crate::{ BaseUrl, BaseUrlError, Host, TryFrom };
+
+/// How BaseUrl::parse_lenient fills in what an address leaves out
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FixupOptions {
+    /// The scheme assumed for an address written without one
+    pub default_scheme: String,
+    /// The scheme assumed instead for `localhost` and IP addresses written without one, which are
+    /// rarely served over TLS
+    pub local_scheme: String,
+}
+
+impl Default for FixupOptions {
+    /// Assumes `https`, or `http` for local and IP addresses
+    fn default( ) -> Self {
+        FixupOptions{ default_scheme: "https".to_owned( ), local_scheme: "http".to_owned( ) }
+    }
+}
+
+/// A change BaseUrl::parse_lenient made to the text before parsing it
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Fixup {
+    /// Whitespace surrounding the address was removed
+    Trimmed,
+    /// Slashes or backslashes before an address without a scheme, as in `//example.org`, were removed
+    LeadingSlashesRemoved,
+    /// The address had no scheme, so the scheme held was assumed
+    SchemeAdded( String ),
+}
+
+pub(crate) fn parse_lenient( input:&str, options:&FixupOptions ) -> Result< ( BaseUrl, Vec< Fixup > ), BaseUrlError > {
+    let mut fixups = Vec::new( );
+    let trimmed = input.trim( );
+    if trimmed.len( ) != input.len( ) {
+        fixups.push( Fixup::Trimmed );
+    }
+
+    if has_scheme( trimmed ) {
+        return BaseUrl::try_from( trimmed ).map( |url| ( url, fixups ) );
+    }
+
+    let authority = trimmed.trim_start_matches( [ '/', '\\' ] );
+    if authority.len( ) != trimmed.len( ) {
+        fixups.push( Fixup::LeadingSlashesRemoved );
+    }
+    let mut url = BaseUrl::try_from( format!( "{}://{}", options.default_scheme, authority ).as_str( ) )?;
+    let local = match url.host( ) {
+        Host::Domain( domain ) => domain == "localhost" || domain.ends_with( ".localhost" ),
+        Host::Ipv4( _ ) | Host::Ipv6( _ ) => true,
+    };
+    let mut scheme = options.default_scheme.as_str( );
+    if local && options.local_scheme != options.default_scheme {
+        url = BaseUrl::try_from( format!( "{}://{}", options.local_scheme, authority ).as_str( ) )?;
+        scheme = &options.local_scheme;
+    }
+    fixups.push( Fixup::SchemeAdded( scheme.to_ascii_lowercase( ) ) );
+    Ok( ( url, fixups ) )
+}
+
+/// Schemes recognised without a following `//`
+const KNOWN_SCHEMES:&[&str] = &[ "about", "blob", "data", "file", "ftp", "http", "https", "javascript", "mailto",
+                                 "news", "sms", "tel", "urn", "ws", "wss" ];
+
+/// Returns true if the text begins with a scheme, rather than a host and port such as
+/// `localhost:3000` or userinfo such as `user:pass@host` which would otherwise read as one. Only a
+/// known scheme, or one followed by `//`, is taken as a scheme.
+fn has_scheme( input:&str ) -> bool {
+    let colon = match input.find( |c:char| !c.is_ascii_alphanumeric( ) && c != '+' && c != '-' && c != '.' ) {
+        Some( colon ) if colon > 0 && input[colon..].starts_with( ':' ) => colon,
+        _ => return false,
+    };
+    if !input.starts_with( |c:char| c.is_ascii_alphabetic( ) ) {
+        return false;
+    }
+    let after = &input[colon + 1..];
+    if !after.starts_with( "//" ) && !KNOWN_SCHEMES.contains( &input[..colon].to_ascii_lowercase( ).as_str( ) ) {
+        return false;
+    }
+    let port_end = after.find( [ '/', '?', '#' ] ).unwrap_or( after.len( ) );
+    let is_port = port_end > 0 && after[..port_end].bytes( ).all( |b| b.is_ascii_digit( ) );
+    !is_port
+}
@@ -0,0 +1,323 @@
+/*!
+Reconstructing the BaseUrl a client used to reach an application running behind reverse proxies and
+load balancers.
+
+Proxies describe the original request with either the standard `Forwarded` header of
+[RFC 7239](https://www.rfc-editor.org/rfc/rfc7239) or the de-facto `X-Forwarded-For`,
+`X-Forwarded-Proto`, `X-Forwarded-Host`, `X-Forwarded-Port` and `X-Forwarded-Prefix` headers.
+`Forwarded` is used when present, the `X-Forwarded-*` headers otherwise.
+
+These headers can be set by anyone, so only the hops added by trusted proxies are believed. Each
+proxy appends to the headers, so they are read from the end: the last hop was added by the peer of
+the connection, and each hop names the client of the proxy that added it. Reading stops at the first
+hop added by an untrusted proxy, and only the values of the furthest trusted hop are used; whatever
+it leaves out is kept from the BaseUrl the application sees, never taken from a closer hop.
+
+`X-Forwarded-Proto`, `X-Forwarded-Host`, `X-Forwarded-Port` and `X-Forwarded-Prefix` carry a single
+value rather than one per hop, so only their last value is read. The trusted proxy closest to the
+application must overwrite them, as a value sent by the client is otherwise believed.
+
+```
+use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+use base_url::forwarded::{ external_base_url, TrustedProxies };
+use base_url::host_matcher::Cidr;
+
+# fn run( ) -> Result< (), BaseUrlError > {
+let local = BaseUrl::try_from( "http://10.0.3.7:8080/" )?;
+let trusted = TrustedProxies::Addresses( vec![ Cidr::parse( "10.0.0.0/8" ).unwrap( ) ] );
+let headers = vec![
+    ( "Forwarded", "for=203.0.113.9;proto=https;host=shop.example.org, for=10.0.1.2" ),
+];
+
+let external = external_base_url( &local, headers.clone( ), Some( "10.0.0.5".parse( ).unwrap( ) ), &trusted );
+assert_eq!( external.as_str( ), "https://shop.example.org/" );
+
+// The same headers arriving directly from a client are not believed
+let external = external_base_url( &local, headers, Some( "198.51.100.1".parse( ).unwrap( ) ), &trusted );
+assert_eq!( external.as_str( ), "http://10.0.3.7:8080/" );
+# Ok( () )
+# }
+# run( );
+```
+ */
+
+use crate::BaseUrl;
+use crate::host_matcher::Cidr;
+
+use std::net::IpAddr;
+
+/// Which proxies are trusted to describe the original request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TrustedProxies {
+    /// Ignore the headers entirely
+    None,
+    /// Believe every hop. Only suitable when the application cannot be reached except through proxies
+    /// which overwrite the headers
+    All,
+    /// Believe this many hops, counting back from the peer of the connection
+    Hops( usize ),
+    /// Believe hops added by proxies with these addresses
+    Addresses( Vec< Cidr > ),
+}
+
+impl TrustedProxies {
+
+    /// Returns true if the proxy at the given distance from the application, 0 being the peer, with
+    /// the given address is trusted
+    fn trusts( &self, distance:usize, addr:Option< IpAddr > ) -> bool {
+        match self {
+            TrustedProxies::None => false,
+            TrustedProxies::All => true,
+            TrustedProxies::Hops( n ) => distance < *n,
+            TrustedProxies::Addresses( blocks ) => addr.is_some_and( |a| blocks.iter( ).any( |b| b.contains( a ) ) ),
+        }
+    }
+}
+
+/// What one hop says about the request it received
+#[derive(Clone, Debug, Default)]
+struct Hop {
+    client: Option< IpAddr >,
+    proto: Option< String >,
+    host: Option< String >,
+    port: Option< u16 >,
+}
+
+/// Returns the BaseUrl the client used to reach the application.
+///
+/// `local` is the BaseUrl as the application sees it, `headers` the name and value of every request
+/// header, and `peer` the address of the other end of the connection if it is known. The scheme,
+/// host and port of `local` are replaced by those the furthest trusted hop describes, and any prefix
+/// given by `X-Forwarded-Prefix` is put before its path, whether the hops were read from `Forwarded`
+/// or from the other `X-Forwarded-*` headers. Malformed values are ignored.
+///
+/// # Examples
+///
+/// ```rust
+/// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+/// use base_url::forwarded::{ external_base_url, TrustedProxies };
+///
+///# fn run( ) -> Result< (), BaseUrlError > {
+/// let local = BaseUrl::try_from( "http://127.0.0.1:3000/reports/" )?;
+/// let headers = vec![
+///     ( "X-Forwarded-For", "evil, 198.51.100.20" ),
+///     ( "X-Forwarded-Proto", "https" ),
+///     ( "X-Forwarded-Host", "example.com" ),
+///     ( "X-Forwarded-Port", "8443" ),
+///     ( "X-Forwarded-Prefix", "/app/" ),
+/// ];
+///
+/// let external = external_base_url( &local, headers, None, &TrustedProxies::Hops( 1 ) );
+/// assert_eq!( external.as_str( ), "https://example.com:8443/app/reports/" );
+///
+/// // An edge proxy in front of an internal load balancer, each adding a hop
+/// let headers = vec![
+///     ( "Forwarded", "for=203.0.113.9;proto=https;host=shop.example.org" ),
+///     ( "Forwarded", "for=10.0.1.2;proto=http;host=\"internal-lb:8080\"" ),
+///     ( "X-Forwarded-Prefix", "/shop" ),
+/// ];
+/// let external = external_base_url( &local, headers, None, &TrustedProxies::Hops( 2 ) );
+/// assert_eq!( external.as_str( ), "https://shop.example.org/shop/reports/" );
+///
+/// // A host the edge proxy leaves out is not taken from the load balancer
+/// let headers = vec![
+///     ( "Forwarded", "for=203.0.113.9;proto=https" ),
+///     ( "Forwarded", "for=10.0.1.2;host=internal-lb:8080" ),
+/// ];
+/// let external = external_base_url( &local, headers, None, &TrustedProxies::Hops( 2 ) );
+/// assert_eq!( external.as_str( ), "https://127.0.0.1:3000/reports/" );
+///# Ok( () )
+///# }
+///# run( );
+/// ```
+pub fn external_base_url< I, K, V >( local:&BaseUrl, headers:I, peer:Option< IpAddr >, trusted:&TrustedProxies ) -> BaseUrl
+where I: IntoIterator< Item = ( K, V ) >, K: AsRef< str >, V: AsRef< str > {
+    let mut forwarded = Vec::new( );
+    let mut x_for = Vec::new( );
+    let mut x_proto = Vec::new( );
+    let mut x_host = Vec::new( );
+    let mut x_port = Vec::new( );
+    let mut x_prefix = Vec::new( );
+    for ( name, value ) in headers {
+        let value = value.as_ref( );
+        let list = match name.as_ref( ).to_ascii_lowercase( ).as_str( ) {
+            "forwarded" => {
+                forwarded.extend( parse_forwarded( value ) );
+                continue;
+            }
+            "x-forwarded-for" => &mut x_for,
+            "x-forwarded-proto" => &mut x_proto,
+            "x-forwarded-host" => &mut x_host,
+            "x-forwarded-port" => &mut x_port,
+            "x-forwarded-prefix" => &mut x_prefix,
+            _ => continue,
+        };
+        list.extend( value.split( ',' ).map( |v| v.trim( ).to_owned( ) ) );
+    }
+
+    // Proxies overwrite rather than append to these headers, so only the last value is read
+    let last = |list:&[String]| list.last( ).cloned( );
+    let ( hop, prefix ) = if !forwarded.is_empty( ) {
+        let hops = trusted_hops( &forwarded, peer, trusted );
+        let prefix = if hops.is_empty( ) { None } else { last( &x_prefix ) };
+        ( hops.last( ).cloned( ), prefix )
+    } else {
+        let clients:Vec< Hop > = x_for.iter( ).map( |v| Hop{ client: parse_node( v ), ..Hop::default( ) } ).collect( );
+        let believed = if clients.is_empty( ) {
+            trusted.trusts( 0, peer )
+        } else {
+            !trusted_hops( &clients, peer, trusted ).is_empty( )
+        };
+        if believed {
+            let hop = Hop{
+                client: None,
+                proto: last( &x_proto ),
+                host: last( &x_host ),
+                port: last( &x_port ).and_then( |p| p.parse( ).ok( ) ),
+            };
+            ( Some( hop ), last( &x_prefix ) )
+        } else {
+            ( None, None )
+        }
+    };
+
+    // Only the furthest trusted hop is used, anything it leaves out is taken from local
+    let mut external = local.clone( );
+    if let Some( hop ) = &hop {
+        apply( &mut external, hop );
+    }
+    if let Some( prefix ) = prefix {
+        let prefix = prefix.trim_end_matches( '/' );
+        if prefix.starts_with( '/' ) && !prefix.contains( [ '?', '#' ] ) {
+            let path = format!( "{}{}", prefix, external.path( ) );
+            external.set_path( &path );
+        }
+    }
+    external
+}
+
+/// The hops which trusted proxies added, closest first
+fn trusted_hops( hops:&[Hop], peer:Option< IpAddr >, trusted:&TrustedProxies ) -> Vec< Hop > {
+    let mut accepted = Vec::new( );
+    let mut adder = peer;
+    for ( distance, hop ) in hops.iter( ).rev( ).enumerate( ) {
+        if !trusted.trusts( distance, adder ) {
+            break;
+        }
+        accepted.push( hop.clone( ) );
+        adder = hop.client;
+    }
+    accepted
+}
+
+fn apply( url:&mut BaseUrl, hop:&Hop ) {
+    if let Some( proto ) = &hop.proto {
+        let proto = proto.to_ascii_lowercase( );
+        if proto == "http" || proto == "https" {
+            // Both are special schemes, so changing between them cannot fail
+            let _ = url.set_scheme( &proto );
+        }
+    }
+    if let Some( ( host, port ) ) = hop.host.as_ref( ).and_then( |h| split_authority( h ) ) {
+        let mut candidate = url.clone( );
+        if candidate.set_host( host ).is_ok( ) {
+            candidate.set_port( port );
+            *url = candidate;
+        }
+    }
+    if let Some( port ) = hop.port {
+        url.set_port( Some( port ) );
+    }
+}
+
+/// Split a `Host` header style value into its host and optional port. Returns None if the port is
+/// not a number or an IPv6 address is missing its closing bracket.
+pub(crate) fn split_authority( value:&str ) -> Option< ( &str, Option< u16 > ) > {
+    let colon = if value.starts_with( '[' ) {
+        let end = value.find( ']' )?;
+        match &value[end + 1..] {
+            "" => None,
+            rest if rest.starts_with( ':' ) => Some( end + 1 ),
+            _ => return None,
+        }
+    } else {
+        value.rfind( ':' )
+    };
+    match colon {
+        Some( i ) if i + 1 == value.len( ) => Some( ( &value[..i], None ) ),
+        Some( i ) => Some( ( &value[..i], Some( value[i + 1..].parse( ).ok( )? ) ) ),
+        None => Some( ( value, None ) ),
+    }
+}
+
+/// Parse a node identifier such as `192.0.2.43`, `"[2001:db8::1]:4711"` or `unknown`, keeping only
+/// the address
+fn parse_node( value:&str ) -> Option< IpAddr > {
+    let value = value.trim( ).trim_matches( '"' );
+    if let Ok( addr ) = value.parse( ) {
+        return Some( addr );
+    }
+    let ( host, _ ) = split_authority( value )?;
+    host.trim_start_matches( '[' ).trim_end_matches( ']' ).parse( ).ok( )
+}
+
+/// Parse the elements of a Forwarded header, each a `;` separated list of `name=value` pairs
+fn parse_forwarded( value:&str ) -> Vec< Hop > {
+    let mut hops = Vec::new( );
+    let mut hop = Hop::default( );
+    let mut chars = value.chars( ).peekable( );
+
+    loop {
+        // name
+        let mut name = String::new( );
+        while let Some( &c ) = chars.peek( ) {
+            if c == '=' || c == ';' || c == ',' {
+                break;
+            }
+            name.push( c );
+            chars.next( );
+        }
+        // value, either a token or a quoted-string
+        let mut pair_value = String::new( );
+        if chars.peek( ) == Some( &'=' ) {
+            chars.next( );
+            while chars.peek( ).is_some_and( |c| *c == ' ' || *c == '\t' ) {
+                chars.next( );
+            }
+            if chars.peek( ) == Some( &'"' ) {
+                chars.next( );
+                while let Some( c ) = chars.next( ) {
+                    match c {
+                        '"' => break,
+                        '\\' => pair_value.extend( chars.next( ) ),
+                        c => pair_value.push( c ),
+                    }
+                }
+            }
+            while let Some( &c ) = chars.peek( ) {
+                if c == ';' || c == ',' {
+                    break;
+                }
+                pair_value.push( c );
+                chars.next( );
+            }
+        }
+
+        let pair_value = pair_value.trim( );
+        match name.trim( ).to_ascii_lowercase( ).as_str( ) {
+            "for" => hop.client = parse_node( pair_value ),
+            "proto" => hop.proto = Some( pair_value.to_owned( ) ),
+            "host" => hop.host = Some( pair_value.to_owned( ) ),
+            _ => {}
+        }
+
+        match chars.next( ) {
+            Some( ';' ) => {}
+            Some( _ ) => hops.push( std::mem::take( &mut hop ) ),
+            None => {
+                hops.push( hop );
+                return hops;
+            }
+        }
+    }
+}
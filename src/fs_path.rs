@@ -0,0 +1,128 @@
+/*!
+Mapping the Urls under a BaseUrl to the files under a root directory and back again, as a static file
+server or an offline mirror does. Every segment is checked so that no Url can name a file outside of
+the root.
+ */
+
+use crate::{ BaseUrl, Url };
+
+use url::percent_encoding::{ percent_decode, utf8_percent_encode, PATH_SEGMENT_ENCODE_SET };
+
+use std::path::{ Component, Path, PathBuf };
+
+/// The reasons a Url and a file path cannot be mapped to one another
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FsPathError {
+    /// The Url does not share the scheme, host and port of the BaseUrl or is not below its path
+    OutsideBase,
+    /// The file path is not below the root directory
+    OutsideRoot,
+    /// A segment or component is `.` or `..`
+    DotSegment,
+    /// A segment holds an encoded `/` or `\`
+    EncodedSeparator,
+    /// A segment holds an encoded NUL byte
+    NulByte,
+    /// A segment or component would be read as an absolute path or a drive, such as `C:`
+    AbsoluteComponent,
+    /// A segment is not valid UTF-8 once decoded
+    InvalidUtf8,
+    /// A component of the file path is not valid Unicode
+    NotUnicode,
+}
+
+pub(crate) fn to_fs_path( base:&BaseUrl, root:&Path, url:&Url, index:Option< &str > ) -> Result< PathBuf, FsPathError > {
+    let same_origin = url.scheme( ) == base.scheme( )
+        && url.host_str( ) == Some( base.host_str( ) )
+        && url.port_or_known_default( ) == base.port_or_known_default( );
+    if !same_origin {
+        return Err( FsPathError::OutsideBase );
+    }
+
+    let prefix = base.directory_segments( );
+    let segments:Vec< &str > = url.path_segments( ).ok_or( FsPathError::OutsideBase )?.collect( );
+    if !segments.starts_with( &prefix ) {
+        return Err( FsPathError::OutsideBase );
+    }
+
+    let rest = &segments[prefix.len( )..];
+    let mut path = root.to_path_buf( );
+    for segment in rest.iter( ).filter( |s| !s.is_empty( ) ) {
+        let bytes:Vec< u8 > = percent_decode( segment.as_bytes( ) ).collect( );
+        if bytes.contains( &0 ) {
+            return Err( FsPathError::NulByte );
+        }
+        if bytes.contains( &b'/' ) || bytes.contains( &b'\\' ) {
+            return Err( FsPathError::EncodedSeparator );
+        }
+        let name = String::from_utf8( bytes ).map_err( |_| FsPathError::InvalidUtf8 )?;
+        check_component( &name )?;
+        path.push( name );
+    }
+
+    let directory = rest.last( ).is_none_or( |last| last.is_empty( ) );
+    if let ( true, Some( index ) ) = ( directory, index ) {
+        path.push( index );
+    }
+    Ok( path )
+}
+
+pub(crate) fn from_fs_path( base:&BaseUrl, root:&Path, path:&Path, index:Option< &str > ) -> Result< BaseUrl, FsPathError > {
+    let relative = path.strip_prefix( root ).map_err( |_| FsPathError::OutsideRoot )?;
+    let mut names = Vec::new( );
+    for component in relative.components( ) {
+        match component {
+            Component::Normal( name ) => {
+                let name = name.to_str( ).ok_or( FsPathError::NotUnicode )?;
+                check_component( name )?;
+                names.push( name );
+            }
+            Component::CurDir => {}
+            Component::ParentDir => return Err( FsPathError::DotSegment ),
+            Component::RootDir | Component::Prefix( _ ) => return Err( FsPathError::AbsoluteComponent ),
+        }
+    }
+
+    let mut directory = names.is_empty( );
+    if index.is_some( ) && names.last( ).cloned( ) == index {
+        names.pop( );
+        directory = true;
+    }
+
+    let mut url_path = String::from( "/" );
+    for segment in base.directory_segments( ) {
+        url_path.push_str( segment );
+        url_path.push( '/' );
+    }
+    for ( i, name ) in names.iter( ).enumerate( ) {
+        if i > 0 {
+            url_path.push( '/' );
+        }
+        // A '\' would be read as a separator in special schemes
+        url_path.extend( utf8_percent_encode( name, PATH_SEGMENT_ENCODE_SET ).map( |s| s.replace( '\\', "%5C" ) ) );
+    }
+    if directory && !url_path.ends_with( '/' ) {
+        url_path.push( '/' );
+    }
+
+    let mut url = base.clone( );
+    url.set_query( None );
+    url.set_fragment( None );
+    url.set_path( &url_path );
+    Ok( url )
+}
+
+/// Check that a name is a single plain component on every platform
+fn check_component( name:&str ) -> Result< (), FsPathError > {
+    if name == "." || name == ".." {
+        return Err( FsPathError::DotSegment );
+    }
+    let mut chars = name.chars( );
+    let drive = chars.next( ).is_some_and( |c| c.is_ascii_alphabetic( ) ) && chars.next( ) == Some( ':' );
+    let mut components = Path::new( name ).components( );
+    let plain = matches!( ( components.next( ), components.next( ) ), ( Some( Component::Normal( _ ) ), None ) );
+    if drive || !plain {
+        return Err( FsPathError::AbsoluteComponent );
+    }
+    Ok( () )
+}
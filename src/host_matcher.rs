@@ -0,0 +1,506 @@
+/*!
+Matching BaseUrls against lists of host rules, such as the approved destinations of outbound calls.
+
+Each rule names a host and may restrict the scheme and port:
+
+* `example.org` matches exactly that domain
+* `*.example.org` matches any subdomain of example.org, but not example.org itself
+* `.example.org` matches example.org and any of its subdomains
+* `*` matches any host
+* `192.0.2.7` and `[2001:db8::1]` match exactly that address
+* `10.0.0.0/8` and `[fd00::]/8` match any address in the block
+* a `:port` suffix, as in `example.com:8443`, restricts the rule to that port
+* a `scheme://` prefix, as in `https://.example.org`, restricts the rule to that scheme
+
+Rules are indexed by their host so a lookup takes time proportional to the number of labels in the
+host, or the number of distinct CIDR prefix lengths, rather than the number of rules.
+
+```
+use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+use base_url::host_matcher::HostMatcher;
+
+# fn run( ) -> Result< (), BaseUrlError > {
+let allowed = HostMatcher::parse_list( vec![ "https://.example.org", "example.com:8443", "10.0.0.0/8", "[::1]" ] ).unwrap( );
+
+assert!( allowed.is_match( &BaseUrl::try_from( "https://api.example.org/v1/" )? ) );
+assert!( !allowed.is_match( &BaseUrl::try_from( "http://api.example.org/v1/" )? ) );
+assert!( allowed.is_match( &BaseUrl::try_from( "http://example.com:8443/" )? ) );
+assert!( !allowed.is_match( &BaseUrl::try_from( "https://example.com/" )? ) );
+assert!( allowed.is_match( &BaseUrl::try_from( "http://10.20.30.40/metrics" )? ) );
+assert!( allowed.is_match( &BaseUrl::try_from( "http://[::1]:3000/" )? ) );
+
+let found = allowed.find( &BaseUrl::try_from( "http://10.1.1.1/" )? ).unwrap( );
+assert_eq!( found.index, 2 );
+assert_eq!( found.rule.as_str( ), "10.0.0.0/8" );
+# Ok( () )
+# }
+# run( );
+```
+ */
+
+use crate::{ BaseUrl, Host };
+
+use std::collections::{ BTreeMap, HashMap };
+use std::fmt::{ Formatter, Display, Result as FormatResult };
+use std::net::IpAddr;
+
+/// The ways in which a rule may be malformed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HostRuleError {
+    /// The rule was empty
+    Empty,
+    /// The scheme before `://` is not a valid scheme
+    InvalidScheme( String ),
+    /// The port is not a number from 0 to 65535
+    InvalidPort( String ),
+    /// The host is not a valid domain or IP address
+    InvalidHost( String ),
+    /// The CIDR prefix length is not a number or is too long for the address
+    InvalidPrefix( String ),
+}
+
+/// A block of IP addresses written in CIDR notation, such as `10.0.0.0/8`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+
+    /// Create a block from an address and a prefix length. Bits of the address beyond the prefix are
+    /// cleared. Returns None if the prefix is longer than the address.
+    pub fn new( addr:IpAddr, prefix:u8 ) -> Option< Self > {
+        if prefix > max_prefix( addr ) {
+            return None;
+        }
+        Some( Cidr{ network: mask( addr, prefix ), prefix } )
+    }
+
+    /// Parse a block such as `192.168.0.0/16`, `fd00::/8` or `[fd00::]/8`. An address without a
+    /// prefix length is a block of one address.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::host_matcher::{ Cidr, HostRuleError };
+    ///
+    /// let block = Cidr::parse( "192.168.12.0/22" ).unwrap( );
+    /// assert!( block.contains( "192.168.15.255".parse( ).unwrap( ) ) );
+    /// assert!( !block.contains( "192.168.16.0".parse( ).unwrap( ) ) );
+    /// assert_eq!( block.to_string( ), "192.168.12.0/22" );
+    ///
+    /// assert_eq!( Cidr::parse( "10.0.0.0/33" ), Err( HostRuleError::InvalidPrefix( "33".to_owned( ) ) ) );
+    /// ```
+    pub fn parse( input:&str ) -> Result< Self, HostRuleError > {
+        let ( addr, prefix ) = match input.rfind( '/' ) {
+            Some( i ) => ( &input[..i], Some( &input[i + 1..] ) ),
+            None => ( input, None ),
+        };
+        let addr = parse_ip( addr ).ok_or_else( || HostRuleError::InvalidHost( addr.to_owned( ) ) )?;
+        let prefix = match prefix {
+            Some( p ) => p.parse( ).map_err( |_| HostRuleError::InvalidPrefix( p.to_owned( ) ) )?,
+            None => max_prefix( addr ),
+        };
+        Cidr::new( addr, prefix ).ok_or_else( || HostRuleError::InvalidPrefix( prefix.to_string( ) ) )
+    }
+
+    /// The first address of the block
+    pub fn network( &self ) -> IpAddr {
+        self.network
+    }
+
+    /// The number of leading bits shared by every address in the block
+    pub fn prefix( &self ) -> u8 {
+        self.prefix
+    }
+
+    /// Returns true if the address lies within the block. IPv4 addresses are never within IPv6 blocks
+    /// nor the reverse.
+    pub fn contains( &self, addr:IpAddr ) -> bool {
+        addr.is_ipv4( ) == self.network.is_ipv4( ) && mask( addr, self.prefix ) == self.network
+    }
+}
+
+impl Display for Cidr {
+    fn fmt( &self, formatter:&mut Formatter ) -> FormatResult {
+        write!( formatter, "{}/{}", self.network, self.prefix )
+    }
+}
+
+fn max_prefix( addr:IpAddr ) -> u8 {
+    if addr.is_ipv4( ) { 32 } else { 128 }
+}
+
+fn mask( addr:IpAddr, prefix:u8 ) -> IpAddr {
+    match addr {
+        IpAddr::V4( v4 ) => {
+            let bits = u32::from( v4 ) & u32::MAX.checked_shl( 32 - u32::from( prefix ) ).unwrap_or( 0 );
+            IpAddr::V4( bits.into( ) )
+        }
+        IpAddr::V6( v6 ) => {
+            let bits = u128::from( v6 ) & u128::MAX.checked_shl( 128 - u32::from( prefix ) ).unwrap_or( 0 );
+            IpAddr::V6( bits.into( ) )
+        }
+    }
+}
+
+/// Parse an IP address, with or without the brackets around an IPv6 address
+fn parse_ip( input:&str ) -> Option< IpAddr > {
+    match input.strip_prefix( '[' ).and_then( |i| i.strip_suffix( ']' ) ) {
+        Some( v6 ) => v6.parse( ).ok( ).filter( |a:&IpAddr| a.is_ipv6( ) ),
+        None => input.parse( ).ok( ),
+    }
+}
+
+/// The hosts a rule matches
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HostPattern {
+    /// Any host, written `*`
+    Any,
+    /// Exactly this domain
+    Domain( String ),
+    /// Any subdomain of this domain, written `*.example.org`
+    Subdomains( String ),
+    /// This domain and any subdomain of it, written `.example.org`
+    DomainAndSubdomains( String ),
+    /// Any IP address in this block. A single address is a block with the full prefix length
+    Ip( Cidr ),
+}
+
+/// A single parsed rule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HostRule {
+    text: String,
+    scheme: Option< String >,
+    pattern: HostPattern,
+    port: Option< u16 >,
+}
+
+impl HostRule {
+
+    /// Parse a single rule
+    ///
+    /// # Errors
+    ///
+    /// Returns a HostRuleError describing the part of the rule which could not be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::host_matcher::{ HostRule, HostPattern, HostRuleError };
+    ///
+    /// let rule = HostRule::parse( "HTTPS://*.Example.ORG:8443" ).unwrap( );
+    /// assert_eq!( rule.scheme( ), Some( "https" ) );
+    /// assert_eq!( rule.pattern( ), &HostPattern::Subdomains( "example.org".to_owned( ) ) );
+    /// assert_eq!( rule.port( ), Some( 8443 ) );
+    ///
+    /// assert_eq!( HostRule::parse( "example.org:http" ), Err( HostRuleError::InvalidPort( "http".to_owned( ) ) ) );
+    /// assert_eq!( HostRule::parse( "exa mple.org" ), Err( HostRuleError::InvalidHost( "exa mple.org".to_owned( ) ) ) );
+    /// ```
+    pub fn parse( input:&str ) -> Result< Self, HostRuleError > {
+        let text = input.trim( );
+        if text.is_empty( ) {
+            return Err( HostRuleError::Empty );
+        }
+
+        let ( scheme, rest ) = match text.find( "://" ) {
+            Some( i ) => {
+                let scheme = &text[..i];
+                let valid = scheme.starts_with( |c:char| c.is_ascii_alphabetic( ) )
+                    && scheme.chars( ).all( |c| c.is_ascii_alphanumeric( ) || c == '+' || c == '-' || c == '.' );
+                if !valid {
+                    return Err( HostRuleError::InvalidScheme( scheme.to_owned( ) ) );
+                }
+                ( Some( scheme.to_ascii_lowercase( ) ), &text[i + 3..] )
+            }
+            None => ( None, text ),
+        };
+        let rest = rest.strip_suffix( '/' ).unwrap_or( rest );
+
+        // A CIDR block, which cannot carry a port
+        if let Some( i ) = rest.rfind( '/' ) {
+            if rest[i + 1..].chars( ).all( |c| c.is_ascii_digit( ) ) {
+                let cidr = Cidr::parse( rest )?;
+                return Ok( HostRule{ text: text.to_owned( ), scheme, pattern: HostPattern::Ip( cidr ), port: None } );
+            }
+        }
+
+        let ( host, port ) = split_port( rest )?;
+        let pattern = if host == "*" {
+            HostPattern::Any
+        } else if let Some( domain ) = host.strip_prefix( "*." ) {
+            HostPattern::Subdomains( parse_domain( domain )? )
+        } else if let Some( domain ) = host.strip_prefix( '.' ) {
+            HostPattern::DomainAndSubdomains( parse_domain( domain )? )
+        } else if let Some( addr ) = parse_ip( host ) {
+            HostPattern::Ip( Cidr{ network: addr, prefix: max_prefix( addr ) } )
+        } else {
+            match Host::parse( host ).map_err( |_| HostRuleError::InvalidHost( host.to_owned( ) ) )? {
+                Host::Domain( domain ) => HostPattern::Domain( trim_dot( domain ) ),
+                Host::Ipv4( addr ) => HostPattern::Ip( Cidr{ network: addr.into( ), prefix: 32 } ),
+                Host::Ipv6( addr ) => HostPattern::Ip( Cidr{ network: addr.into( ), prefix: 128 } ),
+            }
+        };
+
+        Ok( HostRule{ text: text.to_owned( ), scheme, pattern, port } )
+    }
+
+    /// The rule as it was written
+    pub fn as_str( &self ) -> &str {
+        &self.text
+    }
+
+    /// The scheme the rule is restricted to, lower-cased
+    pub fn scheme( &self ) -> Option< &str > {
+        self.scheme.as_deref( )
+    }
+
+    /// The hosts the rule matches
+    pub fn pattern( &self ) -> &HostPattern {
+        &self.pattern
+    }
+
+    /// The port the rule is restricted to
+    pub fn port( &self ) -> Option< u16 > {
+        self.port
+    }
+
+    /// Returns true if the rule matches the given BaseUrl
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::host_matcher::HostRule;
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let rule = HostRule::parse( ".example.org:443" ).unwrap( );
+    /// assert!( rule.matches( &BaseUrl::try_from( "https://example.org/" )? ) );
+    /// assert!( rule.matches( &BaseUrl::try_from( "wss://chat.eu.example.org/" )? ) );
+    /// assert!( !rule.matches( &BaseUrl::try_from( "http://example.org/" )? ) );
+    /// assert!( !rule.matches( &BaseUrl::try_from( "https://notexample.org/" )? ) );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn matches( &self, url:&BaseUrl ) -> bool {
+        let host_matches = match ( &self.pattern, url.host( ) ) {
+            ( HostPattern::Any, _ ) => true,
+            ( HostPattern::Domain( d ), Host::Domain( host ) ) => trim_dot( host ) == *d,
+            ( HostPattern::Subdomains( d ), Host::Domain( host ) ) => is_subdomain( &trim_dot( host ), d ),
+            ( HostPattern::DomainAndSubdomains( d ), Host::Domain( host ) ) => {
+                let host = trim_dot( host );
+                host == *d || is_subdomain( &host, d )
+            }
+            ( HostPattern::Ip( cidr ), Host::Ipv4( addr ) ) => cidr.contains( addr.into( ) ),
+            ( HostPattern::Ip( cidr ), Host::Ipv6( addr ) ) => cidr.contains( addr.into( ) ),
+            _ => false,
+        };
+        host_matches && self.restrictions_match( url )
+    }
+
+    fn restrictions_match( &self, url:&BaseUrl ) -> bool {
+        self.scheme.as_ref( ).is_none_or( |s| s == url.scheme( ) )
+            && self.port.is_none_or( |p| Some( p ) == url.port_or_known_default( ) )
+    }
+}
+
+impl Display for HostRule {
+    fn fmt( &self, formatter:&mut Formatter ) -> FormatResult {
+        formatter.write_str( &self.text )
+    }
+}
+
+fn split_port( input:&str ) -> Result< ( &str, Option< u16 > ), HostRuleError > {
+    let colon = if input.starts_with( '[' ) {
+        match input.find( ']' ) {
+            Some( end ) if input[end + 1..].starts_with( ':' ) => Some( end + 1 ),
+            Some( end ) if end + 1 == input.len( ) => None,
+            _ => return Err( HostRuleError::InvalidHost( input.to_owned( ) ) ),
+        }
+    } else if input.matches( ':' ).count( ) == 1 {
+        input.find( ':' )
+    } else {
+        None
+    };
+    match colon {
+        Some( i ) => {
+            let port = &input[i + 1..];
+            let port = port.parse( ).map_err( |_| HostRuleError::InvalidPort( port.to_owned( ) ) )?;
+            Ok( ( &input[..i], Some( port ) ) )
+        }
+        None => Ok( ( input, None ) ),
+    }
+}
+
+fn parse_domain( input:&str ) -> Result< String, HostRuleError > {
+    match Host::parse( input ) {
+        Ok( Host::Domain( domain ) ) => Ok( trim_dot( domain ) ),
+        _ => Err( HostRuleError::InvalidHost( input.to_owned( ) ) ),
+    }
+}
+
+fn trim_dot< S:AsRef< str > >( domain:S ) -> String {
+    let domain = domain.as_ref( );
+    domain.strip_suffix( '.' ).unwrap_or( domain ).to_owned( )
+}
+
+fn is_subdomain( host:&str, domain:&str ) -> bool {
+    host.len( ) > domain.len( ) + 1
+        && host.ends_with( domain )
+        && host.as_bytes( )[host.len( ) - domain.len( ) - 1] == b'.'
+}
+
+/// The rule which matched a BaseUrl
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HostMatch<'a> {
+    /// The position of the rule in the order rules were added
+    pub index: usize,
+    /// The rule itself
+    pub rule: &'a HostRule,
+}
+
+impl<'a> Display for HostMatch<'a> {
+    fn fmt( &self, formatter:&mut Formatter ) -> FormatResult {
+        write!( formatter, "matched rule {} `{}`", self.index, self.rule )
+    }
+}
+
+/// An indexed set of host rules
+#[derive(Clone, Debug, Default)]
+pub struct HostMatcher {
+    rules: Vec< HostRule >,
+    any: Vec< usize >,
+    domains: HashMap< String, Vec< usize > >,
+    subdomains: HashMap< String, Vec< usize > >,
+    /// Blocks keyed by address family and prefix length, then by network
+    blocks: BTreeMap< ( bool, u8 ), HashMap< IpAddr, Vec< usize > > >,
+}
+
+impl HostMatcher {
+
+    /// Create a matcher with no rules, which matches nothing
+    pub fn new( ) -> Self {
+        HostMatcher::default( )
+    }
+
+    /// Create a matcher from a list of rules
+    ///
+    /// # Errors
+    ///
+    /// Returns the error for the first rule which could not be parsed.
+    pub fn parse_list< I, S >( rules:I ) -> Result< Self, HostRuleError >
+    where I: IntoIterator< Item = S >, S: AsRef< str > {
+        let mut matcher = HostMatcher::new( );
+        for rule in rules {
+            matcher.add( rule.as_ref( ) )?;
+        }
+        Ok( matcher )
+    }
+
+    /// Parse a rule and add it to the matcher
+    ///
+    /// # Errors
+    ///
+    /// Returns the reason the rule could not be parsed, leaving the matcher unchanged.
+    pub fn add( &mut self, rule:&str ) -> Result< (), HostRuleError > {
+        self.add_rule( HostRule::parse( rule )? );
+        Ok( () )
+    }
+
+    /// Add an already parsed rule to the matcher
+    pub fn add_rule( &mut self, rule:HostRule ) {
+        let index = self.rules.len( );
+        match &rule.pattern {
+            HostPattern::Any => self.any.push( index ),
+            HostPattern::Domain( d ) => self.domains.entry( d.clone( ) ).or_default( ).push( index ),
+            HostPattern::Subdomains( d ) => self.subdomains.entry( d.clone( ) ).or_default( ).push( index ),
+            HostPattern::DomainAndSubdomains( d ) => {
+                self.domains.entry( d.clone( ) ).or_default( ).push( index );
+                self.subdomains.entry( d.clone( ) ).or_default( ).push( index );
+            }
+            HostPattern::Ip( cidr ) => {
+                self.blocks.entry( ( cidr.network.is_ipv4( ), cidr.prefix ) )
+                           .or_default( )
+                           .entry( cidr.network )
+                           .or_default( )
+                           .push( index );
+            }
+        }
+        self.rules.push( rule );
+    }
+
+    /// Returns every rule in the order they were added
+    pub fn rules( &self ) -> &[HostRule] {
+        &self.rules
+    }
+
+    /// Returns true if the matcher has no rules
+    pub fn is_empty( &self ) -> bool {
+        self.rules.is_empty( )
+    }
+
+    /// Returns true if any rule matches the BaseUrl
+    pub fn is_match( &self, url:&BaseUrl ) -> bool {
+        self.find( url ).is_some( )
+    }
+
+    /// Find the rule which matches the BaseUrl, preferring the earliest added if several do.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::host_matcher::HostMatcher;
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let denied = HostMatcher::parse_list( &[ "*.internal.example.org", "169.254.0.0/16", "http://*" ] ).unwrap( );
+    ///
+    /// let found = denied.find( &BaseUrl::try_from( "http://db.internal.example.org/" )? ).unwrap( );
+    /// assert_eq!( found.to_string( ), "matched rule 0 `*.internal.example.org`" );
+    ///
+    /// let found = denied.find( &BaseUrl::try_from( "http://example.org/" )? ).unwrap( );
+    /// assert_eq!( found.rule.as_str( ), "http://*" );
+    ///
+    /// assert!( denied.find( &BaseUrl::try_from( "https://example.org/" )? ).is_none( ) );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn find( &self, url:&BaseUrl ) -> Option< HostMatch<'_> > {
+        let mut candidates:Vec< usize > = self.any.clone( );
+        match url.host( ) {
+            Host::Domain( host ) => {
+                let host = trim_dot( host.to_ascii_lowercase( ) );
+                if let Some( found ) = self.domains.get( &host ) {
+                    candidates.extend( found );
+                }
+                let mut rest = host.as_str( );
+                while let Some( dot ) = rest.find( '.' ) {
+                    rest = &rest[dot + 1..];
+                    if let Some( found ) = self.subdomains.get( rest ) {
+                        candidates.extend( found );
+                    }
+                }
+            }
+            Host::Ipv4( addr ) => self.candidate_blocks( addr.into( ), &mut candidates ),
+            Host::Ipv6( addr ) => self.candidate_blocks( addr.into( ), &mut candidates ),
+        }
+        candidates.into_iter( )
+                  .filter( |&i| self.rules[i].restrictions_match( url ) )
+                  .min( )
+                  .map( |index| HostMatch{ index, rule: &self.rules[index] } )
+    }
+
+    fn candidate_blocks( &self, addr:IpAddr, candidates:&mut Vec< usize > ) {
+        for ( &( v4, prefix ), networks ) in &self.blocks {
+            if v4 != addr.is_ipv4( ) {
+                continue;
+            }
+            if let Some( found ) = networks.get( &mask( addr, prefix ) ) {
+                candidates.extend( found );
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+/*!
+Validating the `Host` header, or HTTP/2 `:authority` pseudo-header, of incoming requests against the
+BaseUrls an application is configured to serve, guarding against host header injection.
+
+```
+use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+use base_url::host_validator::{ HostValidator, HostRejection };
+
+# fn run( ) -> Result< (), BaseUrlError > {
+let validator = HostValidator::new( vec![
+    BaseUrl::try_from( "https://example.org/" )?,
+    BaseUrl::try_from( "http://admin.example.org:8080/" )?,
+] );
+
+assert_eq!( validator.validate( "EXAMPLE.org:443" ).unwrap( ).as_str( ), "https://example.org/" );
+assert_eq!( validator.validate( "admin.example.org:8080" ).unwrap( ).as_str( ), "http://admin.example.org:8080/" );
+assert_eq!( validator.validate( "admin.example.org" ), Err( HostRejection::NotAllowed( "admin.example.org".to_owned( ), None ) ) );
+assert_eq!( validator.validate( "evil.example.com:443" ), Err( HostRejection::NotAllowed( "evil.example.com".to_owned( ), Some( 443 ) ) ) );
+# Ok( () )
+# }
+# run( );
+```
+ */
+
+use crate::{ BaseUrl, ParseError, TryFrom };
+use crate::forwarded::split_authority;
+
+use std::collections::HashMap;
+
+/// The reasons a header value can be rejected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HostRejection {
+    /// The value was empty
+    Empty,
+    /// The value is not of the form `host[:port]`
+    Malformed,
+    /// The host could not be parsed
+    InvalidHost( ParseError ),
+    /// The value is well formed but names no configured BaseUrl. Holds the parsed host and port
+    NotAllowed( String, Option< u16 > ),
+}
+
+/// A set of BaseUrls which requests may be addressed to
+#[derive(Clone, Debug, Default)]
+pub struct HostValidator {
+    /// Configured BaseUrls keyed by their host
+    allowed: HashMap< String, Vec< BaseUrl > >,
+}
+
+impl HostValidator {
+
+    /// Create a validator accepting requests addressed to any of the given BaseUrls. Only their hosts
+    /// and ports are considered.
+    pub fn new< I:IntoIterator< Item = BaseUrl > >( allowed:I ) -> Self {
+        let mut validator = HostValidator::default( );
+        for url in allowed {
+            validator.allow( url );
+        }
+        validator
+    }
+
+    /// Accept requests addressed to another BaseUrl
+    pub fn allow( &mut self, url:BaseUrl ) {
+        self.allowed.entry( url.host_str( ).to_owned( ) ).or_default( ).push( url );
+    }
+
+    /// Check a `Host` header value, returning the configured BaseUrl it names.
+    ///
+    /// The host is parsed as set_host( ) would parse it, so case, IDNA and IP address spellings are
+    /// normalized. A missing port stands for the default port of the configured BaseUrl's scheme,
+    /// which must match its port_or_known_default( ).
+    ///
+    /// # Errors
+    ///
+    /// Returns a HostRejection saying why the value names no configured BaseUrl.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::host_validator::{ HostValidator, HostRejection };
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let validator = HostValidator::new( vec![ BaseUrl::try_from( "http://[::1]:3000/" )?,
+    ///                                           BaseUrl::try_from( "https://bücher.example/" )? ] );
+    ///
+    /// assert!( validator.validate( "[0:0::1]:3000" ).is_ok( ) );
+    /// assert!( validator.validate( "xn--bcher-kva.example" ).is_ok( ) );
+    /// assert!( validator.validate( "BÜCHER.example:443" ).is_ok( ) );
+    ///
+    /// assert_eq!( validator.validate( " " ), Err( HostRejection::Empty ) );
+    /// assert_eq!( validator.validate( "[::1]:http" ), Err( HostRejection::Malformed ) );
+    /// assert_eq!( validator.validate( "user@[::1]:3000" ), Err( HostRejection::Malformed ) );
+    /// assert!( match validator.validate( "exa mple.org" ) { Err( HostRejection::InvalidHost( _ ) ) => true, _ => false } );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn validate( &self, value:&str ) -> Result< &BaseUrl, HostRejection > {
+        let value = value.trim( );
+        if value.is_empty( ) {
+            return Err( HostRejection::Empty );
+        }
+        let ( host, port ) = split_authority( value ).ok_or( HostRejection::Malformed )?;
+        if host.is_empty( ) || host.contains( [ '@', '/', '?', '#', '\\' ] ) {
+            return Err( HostRejection::Malformed );
+        }
+
+        let mut parsed = BaseUrl::try_from( "http://localhost/" ).unwrap( );
+        parsed.set_host( host ).map_err( HostRejection::InvalidHost )?;
+        let host = parsed.host_str( );
+
+        let candidates = self.allowed.get( host ).map( |c| c.as_slice( ) ).unwrap_or( &[] );
+        candidates.iter( )
+                  .find( |url| port.or_else( || default_port( url ) ) == url.port_or_known_default( ) )
+                  .ok_or_else( || HostRejection::NotAllowed( host.to_owned( ), port ) )
+    }
+}
+
+/// The default port of the BaseUrl's scheme
+fn default_port( url:&BaseUrl ) -> Option< u16 > {
+    let mut url = url.clone( );
+    url.set_port( None );
+    url.port_or_known_default( )
+}
@@ -0,0 +1,304 @@
+/*!
+Parsing the `Link` header of [RFC 8288](https://www.rfc-editor.org/rfc/rfc8288), resolving its
+targets and anchors against the BaseUrl of the response.
+
+Parsing follows the lenient algorithm of RFC 8288 appendix B: a header may be given as several field
+values, each holding a comma separated list of links, and parsing a field value stops at the first
+link which is malformed. Links whose target cannot be resolved are skipped.
+
+```
+use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+use base_url::link_header::LinkHeader;
+
+# fn run( ) -> Result< (), BaseUrlError > {
+let base = BaseUrl::try_from( "https://api.example.org/v1/items?page=2" )?;
+let links = LinkHeader::parse( &base, vec![
+    "<?page=3>; rel=\"next\", <?page=1>; rel=\"prev first\"",
+    "<https://api.example.org/v1/items?page=9>; rel=last",
+] );
+
+assert_eq!( links.next( ).unwrap( ).as_str( ), "https://api.example.org/v1/items?page=3" );
+assert_eq!( links.prev( ).unwrap( ).as_str( ), "https://api.example.org/v1/items?page=1" );
+assert_eq!( links.first( ), links.prev( ) );
+assert_eq!( links.last( ).unwrap( ).query( ), Some( "page=9" ) );
+# Ok( () )
+# }
+# run( );
+```
+ */
+
+use crate::{ BaseUrl, Url };
+
+use url::percent_encoding::percent_decode;
+
+/// A single link: a target, the relations it has to its context and its target attributes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Link {
+    /// The target, resolved against the BaseUrl
+    pub target: Url,
+    /// The relation types, lowercased, in the order given
+    pub rel: Vec< String >,
+    /// The context given by the `anchor` parameter, resolved against the BaseUrl. None if the context
+    /// is the BaseUrl itself
+    pub anchor: Option< Url >,
+    /// The remaining parameters in the order given, with lowercased names. The values of parameters
+    /// whose name ends in `*` are decoded as RFC 8187 describes
+    pub params: Vec< ( String, String ) >,
+}
+
+impl Link {
+
+    /// Returns true if the link has the given relation type, compared without regard to case
+    pub fn has_rel( &self, rel:&str ) -> bool {
+        self.rel.iter( ).any( |r| r.eq_ignore_ascii_case( rel ) )
+    }
+
+    /// Returns the value of the first parameter with the given name, compared without regard to case
+    pub fn param( &self, name:&str ) -> Option< &str > {
+        self.params.iter( ).find( |( n, _ )| n.eq_ignore_ascii_case( name ) ).map( |( _, v )| v.as_str( ) )
+    }
+
+    /// Returns the title of the link, preferring the encoded `title*` over `title`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::link_header::LinkHeader;
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let base = BaseUrl::try_from( "https://example.org/" )?;
+    /// let links = LinkHeader::parse( &base, vec![
+    ///     "</fr>; rel=alternate; hreflang=fr; title=\"French\"; title*=UTF-8'fr'fran%C3%A7ais",
+    ///     "</de>; rel=alternate; title*=iso-8859-1'de'Deutsch%20f%FCr%20alle",
+    ///     "</en>; rel=alternate; title=\"Plain \\\"quoted\\\"\"",
+    /// ] );
+    ///
+    /// assert_eq!( links.links( )[0].title( ), Some( "français" ) );
+    /// assert_eq!( links.links( )[0].param( "hreflang" ), Some( "fr" ) );
+    /// assert_eq!( links.links( )[1].title( ), Some( "Deutsch für alle" ) );
+    /// assert_eq!( links.links( )[2].title( ), Some( "Plain \"quoted\"" ) );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn title( &self ) -> Option< &str > {
+        self.param( "title*" ).or_else( || self.param( "title" ) )
+    }
+}
+
+/// The links of one or more `Link` header field values
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkHeader {
+    base: BaseUrl,
+    links: Vec< Link >,
+}
+
+impl LinkHeader {
+
+    /// Parse every value of a Link header, resolving targets and anchors against the given BaseUrl,
+    /// which should be the BaseUrl the response was retrieved from.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::link_header::LinkHeader;
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let base = BaseUrl::try_from( "https://example.org/docs/intro" )?;
+    /// let links = LinkHeader::parse( &base, vec![
+    ///     "<chapter2>;REL=\"Next Chapter\" ; anchor=\"#part1\", <style.css>; rel=stylesheet; type=text/css",
+    ///     "</about>; rel=author, not a link, </ignored>; rel=next",
+    /// ] );
+    ///
+    /// assert_eq!( links.links( ).len( ), 3 );
+    /// let chapter = &links.links( )[0];
+    /// assert_eq!( chapter.target.as_str( ), "https://example.org/docs/chapter2" );
+    /// assert_eq!( chapter.rel, vec![ "next", "chapter" ] );
+    /// assert_eq!( chapter.anchor.as_ref( ).unwrap( ).as_str( ), "https://example.org/docs/intro#part1" );
+    /// assert_eq!( links.links( )[1].param( "type" ), Some( "text/css" ) );
+    ///
+    /// // The next link has an anchor, so it says nothing about the BaseUrl itself
+    /// assert_eq!( links.next( ), None );
+    /// assert_eq!( links.rel( "author" ).unwrap( ).target.path( ), "/about" );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn parse< I, S >( base:&BaseUrl, values:I ) -> Self
+    where I: IntoIterator< Item = S >, S: AsRef< str > {
+        let mut links = Vec::new( );
+        for value in values {
+            parse_value( base, value.as_ref( ), &mut links );
+        }
+        LinkHeader{ base: base.clone( ), links }
+    }
+
+    /// Returns every link in the order given
+    pub fn links( &self ) -> &[Link] {
+        &self.links
+    }
+
+    /// Returns an iterator over every link in the order given
+    pub fn iter( &self ) -> std::slice::Iter< '_, Link > {
+        self.links.iter( )
+    }
+
+    /// Returns the first link with the given relation type whose context is the BaseUrl, that is one
+    /// without an anchor or with an anchor resolving to the BaseUrl
+    pub fn rel( &self, rel:&str ) -> Option< &Link > {
+        self.links.iter( )
+                  .filter( |link| link.anchor.as_ref( ).is_none_or( |a| a == self.base.as_ref( ) ) )
+                  .find( |link| link.has_rel( rel ) )
+    }
+
+    /// Returns the target of the `next` link
+    pub fn next( &self ) -> Option< &Url > {
+        self.rel( "next" ).map( |link| &link.target )
+    }
+
+    /// Returns the target of the `prev` link, or failing that the `previous` link
+    pub fn prev( &self ) -> Option< &Url > {
+        self.rel( "prev" ).or_else( || self.rel( "previous" ) ).map( |link| &link.target )
+    }
+
+    /// Returns the target of the `first` link
+    pub fn first( &self ) -> Option< &Url > {
+        self.rel( "first" ).map( |link| &link.target )
+    }
+
+    /// Returns the target of the `last` link
+    pub fn last( &self ) -> Option< &Url > {
+        self.rel( "last" ).map( |link| &link.target )
+    }
+}
+
+impl<'a> IntoIterator for &'a LinkHeader {
+    type Item = &'a Link;
+    type IntoIter = std::slice::Iter< 'a, Link >;
+
+    fn into_iter( self ) -> Self::IntoIter {
+        self.links.iter( )
+    }
+}
+
+/// Parse one field value as in RFC 8288 appendix B.2, appending its links
+fn parse_value( base:&BaseUrl, value:&str, links:&mut Vec< Link > ) {
+    let mut rest = value;
+    loop {
+        rest = rest.trim_start_matches( is_ows );
+        if let Some( r ) = rest.strip_prefix( ',' ) {
+            rest = r;
+            continue;
+        }
+        let r = match rest.strip_prefix( '<' ) {
+            Some( r ) => r,
+            None => return,
+        };
+        let end = match r.find( '>' ) {
+            Some( end ) => end,
+            None => return,
+        };
+        let target = &r[..end];
+        rest = &r[end + 1..];
+        let params = parse_params( &mut rest );
+
+        let target = match base.as_ref( ).join( target ) {
+            Ok( target ) => target,
+            Err( _ ) => continue,
+        };
+        let mut rel = None;
+        let mut anchor = None;
+        let mut others = Vec::new( );
+        for ( name, value ) in params {
+            match name.as_str( ) {
+                "rel" => { rel.get_or_insert( value ); }
+                "anchor" => { anchor.get_or_insert( value ); }
+                _ => others.push( ( name, value ) ),
+            }
+        }
+        let anchor = match anchor.map( |a| base.as_ref( ).join( &a ) ) {
+            Some( Ok( anchor ) ) => Some( anchor ),
+            Some( Err( _ ) ) => continue,
+            None => None,
+        };
+        let rel = rel.unwrap_or_default( )
+                     .split_whitespace( )
+                     .map( |r| r.to_lowercase( ) )
+                     .collect( );
+        links.push( Link{ target, rel, anchor, params: others } );
+    }
+}
+
+/// Parse the parameters following a link target as in RFC 8288 appendix B.3
+fn parse_params( rest:&mut &str ) -> Vec< ( String, String ) > {
+    let mut params = Vec::new( );
+    loop {
+        let r = rest.trim_start_matches( is_ows );
+        let r = match r.strip_prefix( ';' ) {
+            Some( r ) => r.trim_start_matches( is_ows ),
+            None => {
+                *rest = r;
+                return params;
+            }
+        };
+        let end = r.find( |c| is_ows( c ) || c == '=' || c == ';' || c == ',' ).unwrap_or( r.len( ) );
+        let name = r[..end].to_lowercase( );
+        let mut r = r[end..].trim_start_matches( is_ows );
+
+        let mut value = String::new( );
+        if let Some( v ) = r.strip_prefix( '=' ) {
+            r = v.trim_start_matches( is_ows );
+            if let Some( v ) = r.strip_prefix( '"' ) {
+                let mut chars = v.char_indices( );
+                r = "";
+                while let Some( ( i, c ) ) = chars.next( ) {
+                    match c {
+                        '"' => {
+                            r = &v[i + 1..];
+                            break;
+                        }
+                        '\\' => value.extend( chars.next( ).map( |( _, c )| c ) ),
+                        c => value.push( c ),
+                    }
+                }
+            } else {
+                let end = r.find( [ ';', ',' ] ).unwrap_or( r.len( ) );
+                value.push_str( r[..end].trim_end_matches( is_ows ) );
+                r = &r[end..];
+            }
+            if name.ends_with( '*' ) {
+                match decode_ext_value( &value ) {
+                    Some( decoded ) => value = decoded,
+                    None => {
+                        *rest = r;
+                        continue;
+                    }
+                }
+            }
+        }
+        params.push( ( name, value ) );
+        *rest = r;
+    }
+}
+
+/// Decode an RFC 8187 `charset'language'value` extended parameter value. Only the UTF-8 and
+/// ISO-8859-1 charsets are supported
+fn decode_ext_value( value:&str ) -> Option< String > {
+    let mut parts = value.splitn( 3, '\'' );
+    let charset = parts.next( )?;
+    let _language = parts.next( )?;
+    let bytes:Vec< u8 > = percent_decode( parts.next( )?.as_bytes( ) ).collect( );
+    if charset.eq_ignore_ascii_case( "utf-8" ) {
+        String::from_utf8( bytes ).ok( )
+    } else if charset.eq_ignore_ascii_case( "iso-8859-1" ) {
+        Some( bytes.into_iter( ).map( char::from ).collect( ) )
+    } else {
+        None
+    }
+}
+
+fn is_ows( c:char ) -> bool {
+    c == ' ' || c == '\t'
+}
@@ -0,0 +1,208 @@
+/*!
+Finding the Urls written in plain text such as chat messages and logs.
+
+A candidate begins at a scheme such as `https:` or, optionally, at a `www.` host written without one,
+and runs to the next whitespace, `<`, `>`, `"` or backtick. Punctuation which more likely belongs to
+the surrounding sentence is then trimmed from its end: any of `.,:;!?'*_~`, and closing brackets
+which have no matching opening bracket in the candidate, so that a Url wrapped in parentheses loses
+the closing parenthesis while one containing them keeps it.
+
+Only candidates which parse as BaseUrls are found, so Urls like `mailto:` addresses, and words which
+merely look like a scheme followed by a colon, are passed over.
+
+```
+use base_url::linkify::Linkifier;
+
+let text = "Docs are at https://example.org/wiki/Rust_(programming_language). \
+            (see http://example.net/faq), or mailto:help@example.org";
+let found:Vec< _ > = Linkifier::new( ).find_iter( text ).map( |m| m.url.into_string( ) ).collect( );
+
+assert_eq!( found, vec![ "https://example.org/wiki/Rust_(programming_language)", "http://example.net/faq" ] );
+```
+ */
+
+use crate::{ BaseUrl, TryFrom };
+
+use std::ops::Range;
+
+/// A Url found in text
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UrlMatch {
+    /// The byte range of the text the Url was written as
+    pub span: Range< usize >,
+    /// The parsed Url
+    pub url: BaseUrl,
+    /// True if the text had no scheme and `https` was assumed
+    pub inferred_scheme: bool,
+}
+
+/// Finds the Urls in text
+#[derive(Clone, Debug, Default)]
+pub struct Linkifier {
+    infer_https: bool,
+}
+
+impl Linkifier {
+
+    /// Create a Linkifier which only finds Urls written with a scheme
+    pub fn new( ) -> Self {
+        Linkifier::default( )
+    }
+
+    /// Set whether hosts beginning with `www.` written without a scheme are found, assuming `https`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::linkify::Linkifier;
+    ///
+    /// let text = "Try www.example.org/download, not ftp.example.org or brady@www.example.org.";
+    /// assert_eq!( Linkifier::new( ).find_iter( text ).count( ), 0 );
+    ///
+    /// let found:Vec< _ > = Linkifier::new( ).infer_https( true ).find_iter( text ).collect( );
+    /// assert_eq!( found.len( ), 1 );
+    /// assert_eq!( &text[found[0].span.clone( )], "www.example.org/download" );
+    /// assert_eq!( found[0].url.as_str( ), "https://www.example.org/download" );
+    /// assert!( found[0].inferred_scheme );
+    /// ```
+    pub fn infer_https( mut self, infer:bool ) -> Self {
+        self.infer_https = infer;
+        self
+    }
+
+    /// Returns an iterator over the Urls in the text, in the order they appear
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::linkify::Linkifier;
+    ///
+    /// let text = "<https://example.org/a?b=[1]>: \"ssh://git@example.org:2222/repo.git\"... Note: C:\\logs!";
+    /// let found:Vec< _ > = Linkifier::new( ).find_iter( text ).collect( );
+    ///
+    /// assert_eq!( found.len( ), 2 );
+    /// assert_eq!( found[0].span, 1..28 );
+    /// assert_eq!( found[0].url.as_str( ), "https://example.org/a?b=[1]" );
+    /// assert_eq!( found[1].url.host_str( ), "example.org" );
+    /// assert_eq!( found[1].url.port( ), Some( 2222 ) );
+    ///
+    /// // A Url directly after a rejected candidate is still found
+    /// let text = "foo:bar,https://x.org/ ok";
+    /// let found:Vec< _ > = Linkifier::new( ).find_iter( text ).collect( );
+    /// assert_eq!( found.len( ), 1 );
+    /// assert_eq!( &text[found[0].span.clone( )], "https://x.org/" );
+    /// ```
+    pub fn find_iter<'t>( &self, text:&'t str ) -> UrlMatches<'t> {
+        UrlMatches{ text, pos: 0, infer_https: self.infer_https }
+    }
+}
+
+/// An iterator over the Urls in some text, created by Linkifier::find_iter
+#[derive(Clone, Debug)]
+pub struct UrlMatches<'t> {
+    text: &'t str,
+    pos: usize,
+    infer_https: bool,
+}
+
+impl<'t> UrlMatches<'t> {
+
+    /// Returns true if a candidate may begin at the given position, because it does not continue a
+    /// word, host or path
+    fn at_boundary( &self, i:usize ) -> bool {
+        match self.text[..i].chars( ).next_back( ) {
+            None => true,
+            Some( c ) => !c.is_alphanumeric( ) && !"+-.@/:_".contains( c ),
+        }
+    }
+
+    /// Returns the length of the scheme and colon the text begins with, if it is followed by more
+    /// than whitespace
+    fn scheme_len( text:&str ) -> Option< usize > {
+        let colon = text.find( |c:char| !c.is_ascii_alphanumeric( ) && c != '+' && c != '-' && c != '.' )?;
+        if colon == 0 || !text[colon..].starts_with( ':' ) {
+            return None;
+        }
+        match text[colon + 1..].chars( ).next( ) {
+            Some( c ) if !is_delimiter( c ) => Some( colon + 1 ),
+            _ => None,
+        }
+    }
+}
+
+impl<'t> Iterator for UrlMatches<'t> {
+    type Item = UrlMatch;
+
+    fn next( &mut self ) -> Option< Self::Item > {
+        let text = self.text;
+        while let Some( ( offset, c ) ) = text[self.pos..].char_indices( ).next( ) {
+            let start = self.pos + offset;
+            self.pos = start + c.len_utf8( );
+            if !c.is_ascii_alphabetic( ) || !self.at_boundary( start ) {
+                continue;
+            }
+
+            let rest = &text[start..];
+            let www = self.infer_https
+                && rest.get( ..4 ).is_some_and( |w| w.eq_ignore_ascii_case( "www." ) )
+                && rest[4..].chars( ).next( ).is_some_and( char::is_alphanumeric );
+            let prefix = match UrlMatches::scheme_len( rest ) {
+                Some( len ) => len,
+                None if www => 4,
+                None => continue,
+            };
+
+            let end = start + rest.find( is_delimiter ).unwrap_or( rest.len( ) );
+            let candidate = trim_trailing( &text[start..end] );
+            let end = start + candidate.len( );
+            if candidate.len( ) <= prefix {
+                continue;
+            }
+
+            let inferred_scheme = !candidate[..prefix].ends_with( ':' );
+            let parsed = if inferred_scheme {
+                BaseUrl::try_from( format!( "https://{}", candidate ).as_str( ) )
+            } else {
+                BaseUrl::try_from( candidate )
+            };
+            // A rejected candidate may still contain a Url, so scanning carries on inside it
+            if let Ok( url ) = parsed {
+                self.pos = end;
+                return Some( UrlMatch{ span: start..end, url, inferred_scheme } );
+            }
+        }
+        None
+    }
+}
+
+/// Characters which end a candidate
+fn is_delimiter( c:char ) -> bool {
+    c.is_whitespace( ) || c.is_control( ) || c == '<' || c == '>' || c == '"' || c == '`'
+}
+
+/// Trim the punctuation which more likely ends a sentence than a Url
+fn trim_trailing( candidate:&str ) -> &str {
+    let mut candidate = candidate;
+    loop {
+        let last = match candidate.chars( ).next_back( ) {
+            Some( c ) => c,
+            None => return candidate,
+        };
+        let trim = match last {
+            '.' | ',' | ':' | ';' | '!' | '?' | '\'' | '*' | '_' | '~' => true,
+            ')' => unbalanced( candidate, '(', ')' ),
+            ']' => unbalanced( candidate, '[', ']' ),
+            '}' => unbalanced( candidate, '{', '}' ),
+            _ => false,
+        };
+        if !trim {
+            return candidate;
+        }
+        candidate = &candidate[..candidate.len( ) - last.len_utf8( )];
+    }
+}
+
+/// Returns true if the candidate closes more brackets than it opens
+fn unbalanced( candidate:&str, open:char, close:char ) -> bool {
+    candidate.matches( close ).count( ) > candidate.matches( open ).count( )
+}
@@ -0,0 +1,271 @@
+/*!
+Walking through the pages of a paginated API.
+
+A Paginator is an iterator over the BaseUrl of each page. Page number and offset pagination work out
+every following BaseUrl on their own by rewriting a query parameter, leaving the other parameters in
+the order and encoding they were given. Neither knows when the last page has been reached, so they
+go on forever: stop iterating once a page comes back empty, or bound the pages with take( ). Cursor
+pagination and APIs which name the next page in a `Link` header or in the body of the response need
+to be told where to go after each page, and yield nothing more until they are.
+
+```
+use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+use base_url::pagination::Paginator;
+
+# fn run( ) -> Result< (), BaseUrlError > {
+let base = BaseUrl::try_from( "https://api.example.org/items?sort=new&page=1&per_page=50" )?;
+let pages:Vec< _ > = Paginator::page_number( &base, "page", 1 ).take( 3 ).map( BaseUrl::into_string ).collect( );
+
+assert_eq!( pages, vec![
+    "https://api.example.org/items?sort=new&page=1&per_page=50",
+    "https://api.example.org/items?sort=new&page=2&per_page=50",
+    "https://api.example.org/items?sort=new&page=3&per_page=50",
+] );
+# Ok( () )
+# }
+# run( );
+```
+ */
+
+use crate::{ BaseUrl, TryFrom };
+use crate::link_header::LinkHeader;
+
+/// How the BaseUrl of the page after the current one is found
+#[derive(Clone, Debug)]
+enum Strategy {
+    PageNumber{ param: String },
+    Offset{ offset_param: String, limit_param: String, limit: u64 },
+    /// The caller supplies the cursor of each following page
+    Cursor{ param: String },
+    /// The caller supplies each following page
+    Follow,
+}
+
+/// An iterator over the BaseUrls of successive pages
+#[derive(Clone, Debug)]
+pub struct Paginator {
+    strategy: Strategy,
+    /// The page or offset of the page which will be yielded next
+    position: u64,
+    pending: Option< BaseUrl >,
+    last: Option< BaseUrl >,
+}
+
+impl Paginator {
+
+    /// Paginate by page number, held in the query parameter `param`. The first page is the one the
+    /// BaseUrl already names, or `first` if it does not hold the parameter. The Paginator never ends
+    /// on its own, so the caller must stop it.
+    pub fn page_number( base:&BaseUrl, param:&str, first:u64 ) -> Self {
+        let position = query_value( base, param ).unwrap_or( first );
+        let mut paginator = Paginator{
+            strategy: Strategy::PageNumber{ param: param.to_owned( ) },
+            position,
+            pending: None,
+            last: None,
+        };
+        paginator.pending = Some( paginator.at_position( base ) );
+        paginator
+    }
+
+    /// Paginate by offset, held in the query parameter `offset_param`, fetching `limit` items per
+    /// page with the query parameter `limit_param`. The first page is at the offset the BaseUrl
+    /// already names, or 0 if it does not hold the parameter. The Paginator never ends on its own, so
+    /// the caller must stop it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::pagination::Paginator;
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let base = BaseUrl::try_from( "https://api.example.org/search?q=a%20b&fields=id,name&debug&limit=10" )?;
+    /// let mut pages = Paginator::offset( &base, "offset", "limit", 25 );
+    ///
+    /// assert_eq!( pages.next( ).unwrap( ).as_str( ), "https://api.example.org/search?q=a%20b&fields=id,name&debug&limit=25&offset=0" );
+    /// assert_eq!( pages.next( ).unwrap( ).as_str( ), "https://api.example.org/search?q=a%20b&fields=id,name&debug&limit=25&offset=25" );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn offset( base:&BaseUrl, offset_param:&str, limit_param:&str, limit:u64 ) -> Self {
+        let position = query_value( base, offset_param ).unwrap_or( 0 );
+        let mut paginator = Paginator{
+            strategy: Strategy::Offset{
+                offset_param: offset_param.to_owned( ),
+                limit_param: limit_param.to_owned( ),
+                limit,
+            },
+            position,
+            pending: None,
+            last: None,
+        };
+        paginator.pending = Some( paginator.at_position( base ) );
+        paginator
+    }
+
+    /// Paginate with the cursors the API returns, passed on in the query parameter `param`. The first
+    /// page is the BaseUrl itself, and each following page is given with set_cursor( ).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::pagination::Paginator;
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let base = BaseUrl::try_from( "https://api.example.org/events?type=push" )?;
+    /// let mut pages = Paginator::cursor( &base, "after" );
+    /// let mut cursors = vec![ Some( "b2Zmc2V0PTUw" ), None ].into_iter( );
+    /// let mut fetched = Vec::new( );
+    ///
+    /// while let Some( page ) = pages.next( ) {
+    ///     fetched.push( page.into_string( ) );
+    ///     pages.set_cursor( cursors.next( ).unwrap( ) );
+    /// }
+    /// assert_eq!( fetched, vec![ "https://api.example.org/events?type=push",
+    ///                            "https://api.example.org/events?type=push&after=b2Zmc2V0PTUw" ] );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn cursor( base:&BaseUrl, param:&str ) -> Self {
+        Paginator{
+            strategy: Strategy::Cursor{ param: param.to_owned( ) },
+            position: 0,
+            pending: Some( base.clone( ) ),
+            last: None,
+        }
+    }
+
+    /// Paginate by following the next page named by each response, either in a `Link` header given to
+    /// follow_link_header( ) or in the body given to follow_next( ). The first page is the BaseUrl
+    /// itself.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::link_header::LinkHeader;
+    /// use base_url::pagination::Paginator;
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let base = BaseUrl::try_from( "https://api.example.org/v2/repos" )?;
+    /// let mut pages = Paginator::follow( &base );
+    ///
+    /// let first = pages.next( ).unwrap( );
+    /// pages.follow_link_header( &LinkHeader::parse( &first, vec![ "<?page=2>; rel=next" ] ) );
+    /// let second = pages.next( ).unwrap( );
+    /// assert_eq!( second.as_str( ), "https://api.example.org/v2/repos?page=2" );
+    ///
+    /// // A next Url taken from a JSON body is resolved against the page it came from
+    /// pages.follow_next( Some( "/v2/repos?page=3" ) );
+    /// assert_eq!( pages.next( ).unwrap( ).as_str( ), "https://api.example.org/v2/repos?page=3" );
+    ///
+    /// pages.follow_next( None );
+    /// assert_eq!( pages.next( ), None );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn follow( base:&BaseUrl ) -> Self {
+        Paginator{
+            strategy: Strategy::Follow,
+            position: 0,
+            pending: Some( base.clone( ) ),
+            last: None,
+        }
+    }
+
+    /// The BaseUrl which will be yielded next, if it is known
+    pub fn peek( &self ) -> Option< &BaseUrl > {
+        self.pending.as_ref( )
+    }
+
+    /// Give the cursor the last page returned for the page after it, or None if it was the last
+    /// page. The cursor replaces the value of the query parameter the Paginator was created with, and
+    /// is ignored unless it was created with cursor( ).
+    pub fn set_cursor( &mut self, cursor:Option< &str > ) {
+        let param = match &self.strategy {
+            Strategy::Cursor{ param } => param.clone( ),
+            _ => return,
+        };
+        let next = match ( cursor, &self.last ) {
+            ( Some( cursor ), Some( last ) ) if !cursor.is_empty( ) => Some( with_query_value( last, &param, cursor ) ),
+            _ => None,
+        };
+        self.set_next( next );
+    }
+
+    /// Give the `Link` header of the last page, following its `next` link if there is one
+    pub fn follow_link_header( &mut self, links:&LinkHeader ) {
+        let next = links.next( ).and_then( |url| BaseUrl::try_from( url.clone( ) ).ok( ) );
+        self.set_next( next );
+    }
+
+    /// Give the Url of the next page found in the body of the last page, or None if it named none. The
+    /// Url is resolved against the last page.
+    pub fn follow_next( &mut self, next:Option< &str > ) {
+        let next = match ( next, &self.last ) {
+            ( Some( next ), Some( last ) ) if !next.trim( ).is_empty( ) => {
+                last.as_ref( ).join( next.trim( ) ).ok( ).and_then( |url| BaseUrl::try_from( url ).ok( ) )
+            }
+            _ => None,
+        };
+        self.set_next( next );
+    }
+
+    /// Queue the next page, refusing one which would repeat the last page forever
+    fn set_next( &mut self, next:Option< BaseUrl > ) {
+        self.pending = next.filter( |next| self.last.as_ref( ) != Some( next ) );
+    }
+
+    /// The BaseUrl for the current position
+    fn at_position( &self, url:&BaseUrl ) -> BaseUrl {
+        match &self.strategy {
+            Strategy::PageNumber{ param } => with_query_value( url, param, &self.position.to_string( ) ),
+            Strategy::Offset{ offset_param, limit_param, limit } => {
+                let url = with_query_value( url, limit_param, &limit.to_string( ) );
+                with_query_value( &url, offset_param, &self.position.to_string( ) )
+            }
+            _ => url.clone( ),
+        }
+    }
+}
+
+impl Iterator for Paginator {
+    type Item = BaseUrl;
+
+    fn next( &mut self ) -> Option< Self::Item > {
+        let current = self.pending.take( )?;
+        let step = match &self.strategy {
+            Strategy::PageNumber{ .. } => Some( 1 ),
+            Strategy::Offset{ limit, .. } => Some( *limit ),
+            _ => None,
+        };
+        if let Some( step ) = step {
+            self.position = self.position.saturating_add( step );
+            self.pending = Some( self.at_position( &current ) );
+        }
+        self.last = Some( current.clone( ) );
+        Some( current )
+    }
+}
+
+/// The value of the first query parameter named `param`, if it is a number
+fn query_value( url:&BaseUrl, param:&str ) -> Option< u64 > {
+    url.query_pairs( ).find( |( k, _ )| k == param ).and_then( |( _, v )| v.parse( ).ok( ) )
+}
+
+/// The BaseUrl with the value of the query parameter `param` replaced, or the parameter appended if
+/// it is missing. Any later repeats of the parameter are removed, and every other parameter is left
+/// exactly as it was. QueryMap is used rather than query_pairs_mut( ), which would re-encode every
+/// other parameter as it rewrote the query.
+fn with_query_value( url:&BaseUrl, param:&str, value:&str ) -> BaseUrl {
+    let mut query = url.query_map( );
+    query.set( param, Some( value ) );
+    let mut url = url.clone( );
+    url.set_query_map( &query );
+    url
+}
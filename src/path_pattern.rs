@@ -0,0 +1,359 @@
+/*!
+Matching Urls against path patterns mounted under a BaseUrl and extracting the captured values, the
+inverse of expanding a URI Template.
+
+A pattern is a `/` separated list of segments. Each segment is either literal text or a capture
+occupying the whole segment:
+
+* `{name}` captures any single segment as a string
+* `{name:int}` and `{name:uint}` capture a segment which parses as a signed or unsigned integer
+* `{name?}`, `{name:int?}` and so on capture a segment which may be absent
+* `{*name}` captures every remaining segment, possibly none, as a list, and may only end a pattern
+
+Captured values and literal segments are compared after percent-decoding, so `{name}` captures
+`caf%C3%A9` as `café` and an encoded `%2F` is never mistaken for a separator: a tail capture of
+`a/b%2Fc` holds the two segments `a` and `b/c`.
+
+```
+use base_url::{ BaseUrl, BaseUrlError, Url, TryFrom };
+use base_url::path_pattern::{ PathPattern, Capture };
+
+# fn run( ) -> Result< (), BaseUrlError > {
+let base = BaseUrl::try_from( "https://api.example.org/v1/" )?;
+let pattern = PathPattern::new( &base, "/users/{id:uint}/posts/{post_id}" ).unwrap( );
+
+let url = Url::parse( "https://api.example.org/v1/users/42/posts/hello%20world?draft=1" )?;
+let captures = pattern.matches( &url ).unwrap( );
+assert_eq!( captures.get( "id" ), Some( &Capture::Uint( 42 ) ) );
+assert_eq!( captures.get_str( "post_id" ), Some( "hello world" ) );
+
+// Outside of the BaseUrl's path
+assert!( pattern.matches( &Url::parse( "https://api.example.org/v2/users/42/posts/1" )? ).is_none( ) );
+// Not an unsigned integer
+assert!( pattern.matches( &Url::parse( "https://api.example.org/v1/users/me/posts/1" )? ).is_none( ) );
+# Ok( () )
+# }
+# run( );
+```
+ */
+
+use crate::{ BaseUrl, Url };
+
+use url::percent_encoding::{ percent_decode, utf8_percent_encode, PATH_SEGMENT_ENCODE_SET };
+
+use std::fmt::{ Formatter, Display, Result as FormatResult };
+
+/// The ways in which a path pattern can be malformed. Each variant holds the byte offset of the
+/// offending segment
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatternError {
+    /// A `{` was not closed by a `}` at the end of the same segment, or a capture shared its segment
+    /// with literal text
+    PartialSegment( usize ),
+    /// A capture's name was empty or contained characters other than letters, digits and `_`
+    InvalidName( usize ),
+    /// A capture's type was not one of `str`, `int` or `uint`
+    UnknownType( usize ),
+    /// A `{*tail}` capture was followed by further segments
+    TailNotLast( usize ),
+    /// The same name was captured twice
+    DuplicateName( usize ),
+}
+
+/// A captured value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Capture {
+    /// A percent-decoded segment
+    Str( String ),
+    /// The remaining percent-decoded segments, for a tail capture
+    Segments( Vec< String > ),
+    /// A segment captured with the `int` type
+    Int( i64 ),
+    /// A segment captured with the `uint` type
+    Uint( u64 ),
+}
+
+impl Display for Capture {
+    fn fmt( &self, formatter:&mut Formatter ) -> FormatResult {
+        match self {
+            Capture::Str( s ) => s.fmt( formatter ),
+            // Written as a relative path, so that a `/` within a segment stays encoded
+            Capture::Segments( segments ) => {
+                for ( i, segment ) in segments.iter( ).enumerate( ) {
+                    if i > 0 {
+                        formatter.write_str( "/" )?;
+                    }
+                    for part in utf8_percent_encode( segment, PATH_SEGMENT_ENCODE_SET ) {
+                        formatter.write_str( part )?;
+                    }
+                }
+                Ok( () )
+            }
+            Capture::Int( i ) => i.fmt( formatter ),
+            Capture::Uint( u ) => u.fmt( formatter ),
+        }
+    }
+}
+
+/// The values captured by a successful match, in pattern order. Optional captures which were absent
+/// are not included.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Captures {
+    values: Vec< ( String, Capture ) >,
+}
+
+impl Captures {
+
+    /// Returns the value captured under the given name
+    pub fn get( &self, name:&str ) -> Option< &Capture > {
+        self.values.iter( ).find( |( n, _ )| n == name ).map( |( _, c )| c )
+    }
+
+    /// Returns the value captured under the given name if it was captured as a string
+    pub fn get_str( &self, name:&str ) -> Option< &str > {
+        match self.get( name ) {
+            Some( Capture::Str( s ) ) => Some( s ),
+            _ => None,
+        }
+    }
+
+    /// Returns the segments captured under the given name if it was a tail capture
+    pub fn get_segments( &self, name:&str ) -> Option< &[String] > {
+        match self.get( name ) {
+            Some( Capture::Segments( segments ) ) => Some( segments ),
+            _ => None,
+        }
+    }
+
+    /// Iterate over the (name, value) pairs captured
+    pub fn iter( &self ) -> impl Iterator< Item = ( &str, &Capture ) > {
+        self.values.iter( ).map( |( n, c )| ( n.as_str( ), c ) )
+    }
+
+    /// The number of values captured
+    pub fn len( &self ) -> usize {
+        self.values.len( )
+    }
+
+    /// Returns true if nothing was captured
+    pub fn is_empty( &self ) -> bool {
+        self.values.is_empty( )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CaptureType {
+    Str,
+    Int,
+    Uint,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Literal( String ),
+    Capture{ name: String, kind: CaptureType, optional: bool },
+    Tail( String ),
+}
+
+/// A path pattern mounted under a BaseUrl
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathPattern {
+    base: BaseUrl,
+    prefix: Vec< String >,
+    segments: Vec< Segment >,
+}
+
+impl PathPattern {
+
+    /// Parse a pattern relative to the path of the given BaseUrl. A leading `/` on the pattern is
+    /// optional, the pattern always continues from the BaseUrl's directory.
+    ///
+    /// # Errors
+    ///
+    /// A PatternError describing the first problem found is returned if the pattern is malformed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::path_pattern::{ PathPattern, PatternError };
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let base = BaseUrl::try_from( "https://example.org/" )?;
+    ///
+    /// assert!( PathPattern::new( &base, "/files/{*path}" ).is_ok( ) );
+    /// assert_eq!( PathPattern::new( &base, "/files/{*path}/raw" ), Err( PatternError::TailNotLast( 15 ) ) );
+    /// assert_eq!( PathPattern::new( &base, "/files/v{version}" ), Err( PatternError::PartialSegment( 7 ) ) );
+    /// assert_eq!( PathPattern::new( &base, "/{id:float}" ), Err( PatternError::UnknownType( 1 ) ) );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn new( base:&BaseUrl, pattern:&str ) -> Result< Self, PatternError > {
+        let mut prefix:Vec< String > = base.path_segments( ).map( decode ).collect( );
+        // Drop the file name, or the empty segment following a trailing slash
+        prefix.pop( );
+
+        let mut segments = Vec::new( );
+        let body = pattern.strip_prefix( '/' ).unwrap_or( pattern );
+        let mut offset = pattern.len( ) - body.len( );
+
+        for raw in body.split( '/' ) {
+            if let Some( Segment::Tail( _ ) ) = segments.last( ) {
+                return Err( PatternError::TailNotLast( offset ) );
+            }
+            let segment = parse_segment( raw, offset )?;
+            let name = match &segment {
+                Segment::Capture{ name, .. } | Segment::Tail( name ) => Some( name ),
+                Segment::Literal( _ ) => None,
+            };
+            if let Some( name ) = name {
+                if segments.iter( ).any( |s| match s {
+                    Segment::Capture{ name: n, .. } | Segment::Tail( n ) => n == name,
+                    Segment::Literal( _ ) => false,
+                } ) {
+                    return Err( PatternError::DuplicateName( offset ) );
+                }
+            }
+            segments.push( segment );
+            offset += raw.len( ) + 1;
+        }
+
+        Ok( PathPattern{ base: base.clone( ), prefix, segments } )
+    }
+
+    /// Match the given Url against this pattern, returning the captured values on success.
+    ///
+    /// The Url must share the BaseUrl's scheme, host and port and its path must continue from the
+    /// BaseUrl's directory. The query and fragment are ignored. A trailing slash is a segment like
+    /// any other, `/users/{id}` does not match `/users/42/`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, Url, TryFrom };
+    /// use base_url::path_pattern::{ PathPattern, Capture };
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let base = BaseUrl::try_from( "https://example.org/" )?;
+    /// let pattern = PathPattern::new( &base, "/archive/{year:int}/{month:uint?}/{*rest}" ).unwrap( );
+    ///
+    /// let captures = pattern.matches( &Url::parse( "https://example.org/archive/2019/07/a/b%2Fc" )? ).unwrap( );
+    /// assert_eq!( captures.get( "year" ), Some( &Capture::Int( 2019 ) ) );
+    /// assert_eq!( captures.get( "month" ), Some( &Capture::Uint( 7 ) ) );
+    /// assert_eq!( captures.get_segments( "rest" ), Some( &[ "a".to_owned( ), "b/c".to_owned( ) ][..] ) );
+    /// assert_eq!( captures.get( "rest" ).unwrap( ).to_string( ), "a/b%2Fc" );
+    ///
+    /// let captures = pattern.matches( &Url::parse( "https://example.org/archive/2019/index.html" )? ).unwrap( );
+    /// assert_eq!( captures.get( "month" ), None );
+    /// assert_eq!( captures.get_segments( "rest" ), Some( &[ "index.html".to_owned( ) ][..] ) );
+    ///
+    /// let captures = pattern.matches( &Url::parse( "https://example.org/archive/-44" )? ).unwrap( );
+    /// assert_eq!( captures.get( "year" ), Some( &Capture::Int( -44 ) ) );
+    /// assert_eq!( captures.get_segments( "rest" ), Some( &[][..] ) );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn matches( &self, url:&Url ) -> Option< Captures > {
+        if url.scheme( ) != self.base.scheme( )
+            || url.host_str( ) != Some( self.base.host_str( ) )
+            || url.port_or_known_default( ) != self.base.port_or_known_default( ) {
+            return None;
+        }
+
+        let path:Vec< String > = url.path_segments( )?.map( decode ).collect( );
+        if path.len( ) < self.prefix.len( ) || path[..self.prefix.len( )] != self.prefix[..] {
+            return None;
+        }
+
+        let mut captures = Captures::default( );
+        if match_segments( &self.segments, &path[self.prefix.len( )..], &mut captures ) {
+            Some( captures )
+        } else {
+            None
+        }
+    }
+}
+
+fn decode( segment:&str ) -> String {
+    percent_decode( segment.as_bytes( ) ).decode_utf8_lossy( ).into_owned( )
+}
+
+fn parse_segment( raw:&str, offset:usize ) -> Result< Segment, PatternError > {
+    if !raw.contains( [ '{', '}' ] ) {
+        return Ok( Segment::Literal( decode( raw ) ) );
+    }
+    let inner = match raw.strip_prefix( '{' ).and_then( |r| r.strip_suffix( '}' ) ) {
+        Some( inner ) if !inner.contains( [ '{', '}' ] ) => inner,
+        _ => return Err( PatternError::PartialSegment( offset ) ),
+    };
+
+    if let Some( name ) = inner.strip_prefix( '*' ) {
+        return if valid_name( name ) {
+            Ok( Segment::Tail( name.to_owned( ) ) )
+        } else {
+            Err( PatternError::InvalidName( offset ) )
+        };
+    }
+
+    let ( inner, optional ) = match inner.strip_suffix( '?' ) {
+        Some( inner ) => ( inner, true ),
+        None => ( inner, false ),
+    };
+    let ( name, kind ) = match inner.find( ':' ) {
+        Some( i ) => ( &inner[..i], match &inner[i + 1..] {
+            "str" => CaptureType::Str,
+            "int" => CaptureType::Int,
+            "uint" => CaptureType::Uint,
+            _ => return Err( PatternError::UnknownType( offset ) ),
+        } ),
+        None => ( inner, CaptureType::Str ),
+    };
+    if !valid_name( name ) {
+        return Err( PatternError::InvalidName( offset ) );
+    }
+    Ok( Segment::Capture{ name: name.to_owned( ), kind, optional } )
+}
+
+fn valid_name( name:&str ) -> bool {
+    !name.is_empty( ) && name.bytes( ).all( |b| b.is_ascii_alphanumeric( ) || b == b'_' )
+}
+
+/// Match the remaining pattern against the remaining path, backtracking over optional segments
+fn match_segments( pattern:&[Segment], path:&[String], captures:&mut Captures ) -> bool {
+    let ( segment, rest ) = match pattern.split_first( ) {
+        Some( split ) => split,
+        None => return path.is_empty( ),
+    };
+
+    match segment {
+        Segment::Literal( literal ) => {
+            !path.is_empty( ) && path[0] == *literal && match_segments( rest, &path[1..], captures )
+        }
+        Segment::Tail( name ) => {
+            captures.values.push( ( name.clone( ), Capture::Segments( path.to_vec( ) ) ) );
+            true
+        }
+        Segment::Capture{ name, kind, optional } => {
+            let checkpoint = captures.values.len( );
+            if let Some( value ) = path.first( ).and_then( |p| typed( p, *kind ) ) {
+                captures.values.push( ( name.clone( ), value ) );
+                if match_segments( rest, &path[1..], captures ) {
+                    return true;
+                }
+                captures.values.truncate( checkpoint );
+            }
+            *optional && match_segments( rest, path, captures )
+        }
+    }
+}
+
+fn typed( segment:&str, kind:CaptureType ) -> Option< Capture > {
+    match kind {
+        CaptureType::Str if !segment.is_empty( ) => Some( Capture::Str( segment.to_owned( ) ) ),
+        CaptureType::Str => None,
+        CaptureType::Int => segment.parse( ).ok( ).map( Capture::Int ),
+        CaptureType::Uint => segment.parse( ).ok( ).map( Capture::Uint ),
+    }
+}
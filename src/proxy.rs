@@ -0,0 +1,210 @@
+/*!
+Choosing an HTTP proxy from the conventional `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY`
+environment variables.
+
+Each variable may be given in lower or upper case, the lower case spelling wins if both are set. The
+exception is `http_proxy`, which like curl is only read in lower case: CGI servers set `HTTP_PROXY`
+from the `Proxy` header of the request they are handling, so an attacker could otherwise choose the
+proxy (the "httpoxy" vulnerability). Proxies given without a scheme, such as `proxy.internal:3128`,
+are assumed to be `http://` proxies. Empty or unparseable proxy values are ignored, as are
+unparseable `NO_PROXY` entries.
+
+`NO_PROXY` is a comma or space separated list. Following the de-facto semantics shared by curl and
+most HTTP clients:
+
+* `*` on its own disables proxying entirely
+* `example.org`, `.example.org` and `*.example.org` all match example.org and any of its subdomains,
+  but not `notexample.org`
+* IP addresses and CIDR blocks such as `10.0.0.0/8` or `fd00::/8` match hosts given as IP addresses
+* a `:port` suffix restricts an entry to that port
+
+```
+use std::collections::HashMap;
+use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+use base_url::proxy::ProxyConfig;
+
+# fn run( ) -> Result< (), BaseUrlError > {
+let mut vars = HashMap::new( );
+vars.insert( "HTTPS_PROXY".to_owned( ), "http://proxy.corp.example:3128".to_owned( ) );
+vars.insert( "no_proxy".to_owned( ), "localhost,.internal.example, 10.0.0.0/8".to_owned( ) );
+let config = ProxyConfig::from_map( &vars );
+
+let proxy = config.proxy_for( &BaseUrl::try_from( "https://api.github.com/" )? ).unwrap( );
+assert_eq!( proxy.as_str( ), "http://proxy.corp.example:3128/" );
+
+assert!( config.proxy_for( &BaseUrl::try_from( "https://db.internal.example/" )? ).is_none( ) );
+assert!( config.proxy_for( &BaseUrl::try_from( "https://10.1.2.3/" )? ).is_none( ) );
+// No HTTP_PROXY or ALL_PROXY is set
+assert!( config.proxy_for( &BaseUrl::try_from( "http://api.github.com/" )? ).is_none( ) );
+# Ok( () )
+# }
+# run( );
+```
+ */
+
+use crate::{ BaseUrl, TryFrom };
+use crate::host_matcher::{ HostMatcher, HostPattern, HostRule };
+
+use std::collections::HashMap;
+use std::env;
+
+/// Proxy settings read from the environment
+#[derive(Clone, Debug, Default)]
+pub struct ProxyConfig {
+    http: Option< BaseUrl >,
+    https: Option< BaseUrl >,
+    all: Option< BaseUrl >,
+    no_proxy: HostMatcher,
+}
+
+impl ProxyConfig {
+
+    /// Read the proxy settings from the process environment
+    pub fn from_env( ) -> Self {
+        ProxyConfig::from_map( &env::vars( ).collect( ) )
+    }
+
+    /// Read the proxy settings from a map of environment variables
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::proxy::ProxyConfig;
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let mut vars = HashMap::new( );
+    /// vars.insert( "ALL_PROXY".to_owned( ), "socks5://127.0.0.1:1080".to_owned( ) );
+    /// vars.insert( "http_proxy".to_owned( ), "web-cache:8080".to_owned( ) );
+    /// let config = ProxyConfig::from_map( &vars );
+    ///
+    /// assert_eq!( config.http( ).unwrap( ).as_str( ), "http://web-cache:8080/" );
+    /// assert_eq!( config.proxy_for( &BaseUrl::try_from( "https://example.org/" )? ),
+    ///             config.all( ) );
+    ///
+    /// // HTTP_PROXY may have come from a client's `Proxy` header, so it is never read
+    /// vars.remove( "http_proxy" );
+    /// vars.insert( "HTTP_PROXY".to_owned( ), "http://attacker.example:8080".to_owned( ) );
+    /// vars.insert( "REQUEST_METHOD".to_owned( ), "GET".to_owned( ) );
+    /// let config = ProxyConfig::from_map( &vars );
+    /// assert_eq!( config.http( ), None );
+    /// assert_eq!( config.proxy_for( &BaseUrl::try_from( "http://example.org/" )? ), config.all( ) );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn from_map( vars:&HashMap< String, String > ) -> Self {
+        let var = |name:&str| {
+            vars.get( &name.to_ascii_lowercase( ) )
+                .or_else( || vars.get( name ) )
+                .map( |v| v.trim( ) )
+                .filter( |v| !v.is_empty( ) )
+        };
+        let proxy = |name:&str| var( name ).and_then( parse_proxy );
+
+        let mut no_proxy = HostMatcher::new( );
+        if let Some( list ) = var( "NO_PROXY" ) {
+            for entry in list.split( |c:char| c == ',' || c.is_whitespace( ) ).filter( |e| !e.is_empty( ) ) {
+                if let Some( rule ) = no_proxy_rule( entry ) {
+                    no_proxy.add_rule( rule );
+                }
+            }
+        }
+
+        ProxyConfig{
+            http: vars.get( "http_proxy" ).map( |v| v.trim( ) ).and_then( parse_proxy ),
+            https: proxy( "HTTPS_PROXY" ),
+            all: proxy( "ALL_PROXY" ),
+            no_proxy,
+        }
+    }
+
+    /// The proxy for `http` and `ws` targets
+    pub fn http( &self ) -> Option< &BaseUrl > {
+        self.http.as_ref( )
+    }
+
+    /// The proxy for `https` and `wss` targets
+    pub fn https( &self ) -> Option< &BaseUrl > {
+        self.https.as_ref( )
+    }
+
+    /// The proxy for any target without a more specific proxy
+    pub fn all( &self ) -> Option< &BaseUrl > {
+        self.all.as_ref( )
+    }
+
+    /// The hosts which should be reached directly
+    pub fn no_proxy( &self ) -> &HostMatcher {
+        &self.no_proxy
+    }
+
+    /// Returns true if the target is excluded from proxying by `NO_PROXY`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::proxy::ProxyConfig;
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let mut vars = HashMap::new( );
+    /// vars.insert( "NO_PROXY".to_owned( ), "example.org,localhost:8080,[::1],*.test".to_owned( ) );
+    /// let config = ProxyConfig::from_map( &vars );
+    ///
+    /// assert!( config.bypasses( &BaseUrl::try_from( "https://example.org/" )? ) );
+    /// assert!( config.bypasses( &BaseUrl::try_from( "https://www.example.org/" )? ) );
+    /// assert!( !config.bypasses( &BaseUrl::try_from( "https://notexample.org/" )? ) );
+    /// assert!( config.bypasses( &BaseUrl::try_from( "http://localhost:8080/" )? ) );
+    /// assert!( !config.bypasses( &BaseUrl::try_from( "http://localhost:9090/" )? ) );
+    /// assert!( config.bypasses( &BaseUrl::try_from( "http://[::1]:3000/" )? ) );
+    /// assert!( config.bypasses( &BaseUrl::try_from( "http://app.test/" )? ) );
+    ///
+    /// vars.insert( "NO_PROXY".to_owned( ), "*".to_owned( ) );
+    /// assert!( ProxyConfig::from_map( &vars ).bypasses( &BaseUrl::try_from( "https://example.com/" )? ) );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn bypasses( &self, target:&BaseUrl ) -> bool {
+        self.no_proxy.is_match( target )
+    }
+
+    /// Returns the proxy to use for the target, or None if it should be reached directly
+    pub fn proxy_for( &self, target:&BaseUrl ) -> Option< &BaseUrl > {
+        if self.bypasses( target ) {
+            return None;
+        }
+        let specific = match target.scheme( ) {
+            "http" | "ws" => self.http.as_ref( ),
+            "https" | "wss" => self.https.as_ref( ),
+            _ => None,
+        };
+        specific.or( self.all.as_ref( ) )
+    }
+}
+
+fn parse_proxy( value:&str ) -> Option< BaseUrl > {
+    if value.contains( "://" ) {
+        BaseUrl::try_from( value ).ok( )
+    } else {
+        BaseUrl::try_from( format!( "http://{}", value ).as_str( ) ).ok( )
+    }
+}
+
+/// A NO_PROXY entry as a host rule. Domains always cover their subdomains
+fn no_proxy_rule( entry:&str ) -> Option< HostRule > {
+    if entry == "*" {
+        return HostRule::parse( entry ).ok( );
+    }
+    let entry = entry.strip_prefix( '*' ).unwrap_or( entry );
+    if let Ok( rule ) = HostRule::parse( entry ) {
+        if let HostPattern::Ip( _ ) = rule.pattern( ) {
+            return Some( rule );
+        }
+    }
+    let domain = entry.strip_prefix( '.' ).unwrap_or( entry );
+    HostRule::parse( &format!( ".{}", domain ) ).ok( )
+}
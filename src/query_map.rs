@@ -0,0 +1,237 @@
+/*!
+An editable view of the query of a BaseUrl as an ordered list of name and value pairs.
+
+Unlike query_pairs_mut( ), which can only append to or clear the query, a QueryMap can replace, remove,
+rename and reorder pairs before being written back with BaseUrl::set_query_map( ). Pairs which are not
+changed are written back exactly as they were found, keeping their original percent-encoding, while
+changed pairs are written in the `application/x-www-form-urlencoded` format. A name without a value,
+as in `?a`, is kept distinct from one with an empty value, as in `?a=`. Empty pairs, as in `?a=1&&b=2`,
+are dropped.
+ */
+
+use url::form_urlencoded::byte_serialize;
+use url::percent_encoding::percent_decode;
+
+use std::fmt::{ Display, Formatter, Result as FormatResult };
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Pair {
+    /// The text of the pair as it was parsed, None once it has been changed
+    raw: Option< String >,
+    name: String,
+    value: Option< String >,
+}
+
+impl Pair {
+
+    fn new( name:&str, value:Option< &str > ) -> Self {
+        Pair{ raw: None, name: name.to_owned( ), value: value.map( |v| v.to_owned( ) ) }
+    }
+}
+
+/// The pairs of a query, in order
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct QueryMap {
+    pairs: Vec< Pair >,
+}
+
+impl QueryMap {
+
+    /// Create an empty QueryMap
+    pub fn new( ) -> Self {
+        QueryMap::default( )
+    }
+
+    /// Parse a query, without its leading `?`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::QueryMap;
+    ///
+    /// let query = QueryMap::parse( "q=caf%C3%A9+au+lait&flag&empty=&q=%7E" );
+    ///
+    /// assert_eq!( query.get( "q" ), Some( Some( "café au lait" ) ) );
+    /// assert_eq!( query.get_all( "q" ), vec![ Some( "café au lait" ), Some( "~" ) ] );
+    /// assert_eq!( query.get( "flag" ), Some( None ) );
+    /// assert_eq!( query.get( "empty" ), Some( Some( "" ) ) );
+    /// assert_eq!( query.get( "missing" ), None );
+    /// assert_eq!( query.to_string( ), "q=caf%C3%A9+au+lait&flag&empty=&q=%7E" );
+    /// ```
+    pub fn parse( query:&str ) -> Self {
+        let pairs = query.split( '&' )
+                         .filter( |raw| !raw.is_empty( ) )
+                         .map( |raw| {
+                             let ( name, value ) = match raw.find( '=' ) {
+                                 Some( i ) => ( &raw[..i], Some( decode( &raw[i + 1..] ) ) ),
+                                 None => ( raw, None ),
+                             };
+                             Pair{ raw: Some( raw.to_owned( ) ), name: decode( name ), value }
+                         } )
+                         .collect( );
+        QueryMap{ pairs }
+    }
+
+    /// Returns the number of pairs
+    pub fn len( &self ) -> usize {
+        self.pairs.len( )
+    }
+
+    /// Returns true if there are no pairs
+    pub fn is_empty( &self ) -> bool {
+        self.pairs.is_empty( )
+    }
+
+    /// Returns an iterator over the decoded names and values of the pairs, in order
+    pub fn iter( &self ) -> impl Iterator< Item = ( &str, Option< &str > ) > {
+        self.pairs.iter( ).map( |p| ( p.name.as_str( ), p.value.as_deref( ) ) )
+    }
+
+    /// Returns true if a pair has the given name
+    pub fn contains_key( &self, name:&str ) -> bool {
+        self.pairs.iter( ).any( |p| p.name == name )
+    }
+
+    /// Returns the value of the first pair with the given name. The value is None if the pair has no
+    /// `=`, and the result is None if no pair has the name.
+    pub fn get( &self, name:&str ) -> Option< Option< &str > > {
+        self.pairs.iter( ).find( |p| p.name == name ).map( |p| p.value.as_deref( ) )
+    }
+
+    /// Returns the values of every pair with the given name, in order
+    pub fn get_all( &self, name:&str ) -> Vec< Option< &str > > {
+        self.pairs.iter( ).filter( |p| p.name == name ).map( |p| p.value.as_deref( ) ).collect( )
+    }
+
+    /// Add a pair after all others
+    pub fn append( &mut self, name:&str, value:Option< &str > ) {
+        self.pairs.push( Pair::new( name, value ) );
+    }
+
+    /// Set the value of the first pair with the given name and remove any others with it, or append a
+    /// pair if there is none
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let mut url = BaseUrl::try_from( "https://example.org/?a=%7e&page=1&b&page=9" )?;
+    /// let mut query = url.query_map( );
+    /// query.set( "page", Some( "2" ) );
+    /// query.set( "sort", Some( "new first" ) );
+    /// url.set_query_map( &query );
+    ///
+    /// assert_eq!( url.as_str( ), "https://example.org/?a=%7e&page=2&b&sort=new+first" );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn set( &mut self, name:&str, value:Option< &str > ) {
+        match self.pairs.iter( ).position( |p| p.name == name ) {
+            Some( i ) => {
+                self.pairs[i] = Pair::new( name, value );
+                let mut index = 0;
+                self.pairs.retain( |p| {
+                    index += 1;
+                    index <= i + 1 || p.name != name
+                } );
+            }
+            None => self.append( name, value ),
+        }
+    }
+
+    /// Remove every pair with the given name, returning how many were removed
+    pub fn remove( &mut self, name:&str ) -> usize {
+        let before = self.pairs.len( );
+        self.pairs.retain( |p| p.name != name );
+        before - self.pairs.len( )
+    }
+
+    /// Keep only the pairs for which the predicate, given the name and value, returns true
+    pub fn retain< F:FnMut( &str, Option< &str > ) -> bool >( &mut self, mut keep:F ) {
+        self.pairs.retain( |p| keep( &p.name, p.value.as_deref( ) ) );
+    }
+
+    /// Give every pair named `from` the name `to`, returning how many were renamed
+    pub fn rename( &mut self, from:&str, to:&str ) -> usize {
+        let mut renamed = 0;
+        for pair in self.pairs.iter_mut( ).filter( |p| p.name == from ) {
+            *pair = Pair{ raw: None, name: to.to_owned( ), value: pair.value.take( ) };
+            renamed += 1;
+        }
+        renamed
+    }
+
+    /// Sort the pairs by name, keeping pairs with the same name in their existing order. Sorted pairs
+    /// are otherwise unchanged and keep their original encoding.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::QueryMap;
+    ///
+    /// let mut query = QueryMap::parse( "z=1&b=%7e&a&b=1&a=" );
+    /// query.sort( );
+    /// assert_eq!( query.to_string( ), "a&a=&b=%7e&b=1&z=1" );
+    /// ```
+    pub fn sort( &mut self ) {
+        self.pairs.sort_by( |a, b| a.name.cmp( &b.name ) );
+    }
+
+    /// Remove every pair with the same name and value as an earlier pair
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::QueryMap;
+    ///
+    /// let mut query = QueryMap::parse( "tag=a&tag=b&tag=%61&tag&tag" );
+    /// query.dedup( );
+    /// assert_eq!( query.to_string( ), "tag=a&tag=b&tag" );
+    /// ```
+    pub fn dedup( &mut self ) {
+        let mut seen:Vec< ( String, Option< String > ) > = Vec::new( );
+        self.pairs.retain( |p| {
+            let key = ( p.name.clone( ), p.value.clone( ) );
+            if seen.contains( &key ) {
+                false
+            } else {
+                seen.push( key );
+                true
+            }
+        } );
+    }
+}
+
+/// The serialization of the query, without a leading `?`
+impl Display for QueryMap {
+    fn fmt( &self, formatter:&mut Formatter ) -> FormatResult {
+        for ( i, pair ) in self.pairs.iter( ).enumerate( ) {
+            if i > 0 {
+                formatter.write_str( "&" )?;
+            }
+            if let Some( raw ) = &pair.raw {
+                formatter.write_str( raw )?;
+                continue;
+            }
+            for part in byte_serialize( pair.name.as_bytes( ) ) {
+                formatter.write_str( part )?;
+            }
+            if let Some( value ) = &pair.value {
+                formatter.write_str( "=" )?;
+                for part in byte_serialize( value.as_bytes( ) ) {
+                    formatter.write_str( part )?;
+                }
+            }
+        }
+        Ok( () )
+    }
+}
+
+/// Decode a name or value in the `application/x-www-form-urlencoded` format
+fn decode( input:&str ) -> String {
+    let input = input.replace( '+', " " );
+    percent_decode( input.as_bytes( ) ).decode_utf8_lossy( ).into_owned( )
+}
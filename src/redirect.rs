@@ -0,0 +1,216 @@
+/*!
+Following HTTP redirects: resolving each `Location` header against the current BaseUrl and applying
+the policy checks a client needs along the way.
+
+```
+use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+use base_url::redirect::{ RedirectTracker, RedirectError };
+
+# fn run( ) -> Result< (), BaseUrlError > {
+let mut tracker = RedirectTracker::new( BaseUrl::try_from( "https://example.org/docs#install" )? );
+
+let hop = tracker.follow( "/docs/" ).unwrap( );
+assert_eq!( hop.url.as_str( ), "https://example.org/docs/#install" );
+assert!( !hop.cross_origin && !hop.drop_credentials );
+
+let hop = tracker.follow( "http://mirror.example.net/docs/" ).unwrap( );
+assert!( hop.downgrade && hop.cross_origin && hop.drop_credentials );
+
+// Back at the starting origin, but credentials dropped once stay dropped
+let hop = tracker.follow( "https://example.org/docs" ).unwrap( );
+assert!( !hop.cross_origin && hop.drop_credentials );
+
+// Following the first redirect a second time means the chain goes round in circles
+match tracker.follow( "/docs/" ) {
+    Err( RedirectError::Loop( url ) ) => assert_eq!( url.as_str( ), "https://example.org/docs/#install" ),
+    _ => panic!( ),
+}
+# Ok( () )
+# }
+# run( );
+```
+ */
+
+use crate::{ BaseUrl, BaseUrlError, ParseError, TryFrom };
+
+use std::collections::HashSet;
+
+/// The ways in which following a redirect can fail
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RedirectError {
+    /// The Location could not be resolved against the current BaseUrl
+    InvalidLocation( ParseError ),
+    /// The Location resolved to a Url which cannot be a base, such as a `mailto:` Url
+    CannotBeBase,
+    /// The redirect from the current BaseUrl to the Location has already been followed, so the chain
+    /// would repeat forever. Holds the BaseUrl the Location leads to.
+    Loop( BaseUrl ),
+    /// Following the Location would exceed the maximum number of redirects
+    TooManyRedirects( usize ),
+    /// The Location leads from `https` to `http` and downgrades are not allowed
+    Downgrade( BaseUrl ),
+}
+
+/// A single redirect which was followed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Redirect {
+    /// The BaseUrl redirected to
+    pub url: BaseUrl,
+    /// True if the redirect leads from `https` to `http`
+    pub downgrade: bool,
+    /// True if the redirect leads to a different scheme, host or port than the starting BaseUrl,
+    /// which credentials belong to
+    pub cross_origin: bool,
+    /// True if credentials such as an `Authorization` header must not be sent with the next request,
+    /// because this redirect or any before it was cross origin or a downgrade. Credentials dropped at
+    /// one hop are not restored by later hops.
+    pub drop_credentials: bool,
+}
+
+/// Follows a chain of redirects from a starting BaseUrl
+#[derive(Clone, Debug)]
+pub struct RedirectTracker {
+    history: Vec< BaseUrl >,
+    /// Every redirect followed, as serializations without the fragment of where it led from and to
+    followed: HashSet< ( String, String ) >,
+    credentials_dropped: bool,
+    max_hops: usize,
+    allow_downgrades: bool,
+}
+
+impl RedirectTracker {
+
+    /// The default maximum number of redirects, as used by most browsers
+    pub const DEFAULT_MAX_HOPS:usize = 20;
+
+    /// Start tracking redirects from the given BaseUrl
+    pub fn new( start:BaseUrl ) -> Self {
+        RedirectTracker{
+            history: vec![ start ],
+            followed: HashSet::new( ),
+            credentials_dropped: false,
+            max_hops: RedirectTracker::DEFAULT_MAX_HOPS,
+            allow_downgrades: true,
+        }
+    }
+
+    /// Set the maximum number of redirects which may be followed
+    pub fn max_hops( mut self, max_hops:usize ) -> Self {
+        self.max_hops = max_hops;
+        self
+    }
+
+    /// Set whether redirects from `https` to `http` are followed. They are by default, and are
+    /// always reported in Redirect::downgrade.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::redirect::{ RedirectTracker, RedirectError };
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let mut tracker = RedirectTracker::new( BaseUrl::try_from( "https://example.org/" )? ).allow_downgrades( false );
+    /// assert!( match tracker.follow( "http://example.org/" ) { Err( RedirectError::Downgrade( _ ) ) => true, _ => false } );
+    /// assert_eq!( tracker.current( ).as_str( ), "https://example.org/" );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn allow_downgrades( mut self, allow:bool ) -> Self {
+        self.allow_downgrades = allow;
+        self
+    }
+
+    /// The BaseUrl the next request should be sent to
+    pub fn current( &self ) -> &BaseUrl {
+        self.history.last( ).unwrap( )
+    }
+
+    /// Every BaseUrl visited, starting with the first
+    pub fn history( &self ) -> &[BaseUrl] {
+        &self.history
+    }
+
+    /// The number of redirects followed so far
+    pub fn hops( &self ) -> usize {
+        self.history.len( ) - 1
+    }
+
+    /// Follow a redirect, resolving the value of its `Location` header against the current BaseUrl.
+    ///
+    /// As RFC 9110 section 10.2.2 requires, a Location without a fragment inherits the fragment of the
+    /// current BaseUrl.
+    ///
+    /// # Errors
+    ///
+    /// If the Location is invalid, repeats a redirect already followed, exceeds the maximum number of
+    /// redirects or is a forbidden downgrade an error is returned and the tracker is left unchanged.
+    /// Revisiting a BaseUrl is not an error in itself, as a chain such as `/a`, `/login`, `/a` is
+    /// legitimate.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::redirect::{ RedirectTracker, RedirectError };
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let mut tracker = RedirectTracker::new( BaseUrl::try_from( "https://example.org/a/b" )? ).max_hops( 2 );
+    ///
+    /// assert_eq!( tracker.follow( "c?x=1#top" ).unwrap( ).url.as_str( ), "https://example.org/a/c?x=1#top" );
+    /// assert_eq!( tracker.follow( "//example.org:8443/" ).unwrap( ).cross_origin, true );
+    /// assert_eq!( tracker.follow( "/d" ), Err( RedirectError::TooManyRedirects( 2 ) ) );
+    /// assert_eq!( tracker.hops( ), 2 );
+    ///
+    /// let mut tracker = RedirectTracker::new( BaseUrl::try_from( "https://example.org/" )? );
+    /// assert_eq!( tracker.follow( "mailto:help@example.org" ), Err( RedirectError::CannotBeBase ) );
+    ///
+    /// let mut tracker = RedirectTracker::new( BaseUrl::try_from( "https://example.org/a" )? );
+    /// assert!( tracker.follow( "https://sso.example.net/login" ).unwrap( ).drop_credentials );
+    /// assert!( tracker.follow( "/login/2fa" ).unwrap( ).drop_credentials );
+    /// assert!( tracker.follow( "https://example.org/a" ).is_ok( ) );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn follow( &mut self, location:&str ) -> Result< Redirect, RedirectError > {
+        let current = self.current( );
+        let mut next = current.as_ref( ).join( location.trim( ) ).map_err( RedirectError::InvalidLocation )?;
+        if next.fragment( ).is_none( ) {
+            next.set_fragment( current.fragment( ) );
+        }
+        let next = BaseUrl::try_from( next ).map_err( |e| match e {
+            BaseUrlError::ParseError( e ) => RedirectError::InvalidLocation( e ),
+            BaseUrlError::CannotBeBase => RedirectError::CannotBeBase,
+        } )?;
+
+        if self.hops( ) >= self.max_hops {
+            return Err( RedirectError::TooManyRedirects( self.max_hops ) );
+        }
+        let edge = ( without_fragment( current ), without_fragment( &next ) );
+        if self.followed.contains( &edge ) {
+            let first = self.history.iter( ).find( |url| without_fragment( url ) == edge.1 ).unwrap( );
+            return Err( RedirectError::Loop( first.clone( ) ) );
+        }
+        let downgrade = current.scheme( ) == "https" && next.scheme( ) == "http";
+        if downgrade && !self.allow_downgrades {
+            return Err( RedirectError::Downgrade( next ) );
+        }
+        let start = &self.history[0];
+        let cross_origin = start.scheme( ) != next.scheme( )
+            || start.host( ) != next.host( )
+            || start.port_or_known_default( ) != next.port_or_known_default( );
+
+        self.credentials_dropped |= downgrade || cross_origin;
+        self.followed.insert( edge );
+        self.history.push( next.clone( ) );
+        Ok( Redirect{ url: next, downgrade, cross_origin, drop_credentials: self.credentials_dropped } )
+    }
+}
+
+fn without_fragment( url:&BaseUrl ) -> String {
+    let mut url = url.clone( );
+    url.set_fragment( None );
+    url.into_string( )
+}
@@ -0,0 +1,699 @@
+/*!
+Reading sitemaps and sitemap indexes as described by the [sitemaps.org protocol](https://www.sitemaps.org/protocol.html),
+enabled by the `sitemap` feature.
+
+Entries are streamed one at a time from any `BufRead`, so very large sitemaps never need to be held
+in memory. With the `gzip` feature enabled (the default) gzip compressed sitemaps are detected and
+decompressed transparently.
+
+Every `<loc>` is resolved against the BaseUrl the sitemap was fetched from and checked against the
+protocol's scope rule: a sitemap may only list Urls with the same scheme, host and port as itself,
+found in the same directory as the sitemap or below it.
+
+Sitemaps can be produced with a SitemapWriter, which applies the same scope rule and the protocol's
+size limits.
+
+```
+use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+use base_url::sitemap::{ SitemapReader, SitemapKind, ChangeFreq };
+
+# fn run( ) -> Result< (), BaseUrlError > {
+let base = BaseUrl::try_from( "https://example.org/blog/sitemap.xml" )?;
+let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>https://example.org/blog/first-post?utm_source=a&amp;page=1</loc>
+    <lastmod>2005-01-01</lastmod>
+    <changefreq>monthly</changefreq>
+    <priority>0.8</priority>
+  </url>
+  <url><loc>second-post</loc></url>
+  <url><loc>https://example.org/shop/</loc></url>
+</urlset>"#;
+
+let mut reader = SitemapReader::new( xml.as_bytes( ), &base ).unwrap( );
+assert_eq!( reader.kind( ), SitemapKind::UrlSet );
+
+let first = reader.next( ).unwrap( ).unwrap( );
+assert_eq!( first.loc.as_str( ), "https://example.org/blog/first-post?utm_source=a&page=1" );
+assert_eq!( first.lastmod.as_ref( ).map( |s| s.as_str( ) ), Some( "2005-01-01" ) );
+assert_eq!( first.changefreq, Some( ChangeFreq::Monthly ) );
+assert_eq!( first.priority, Some( 0.8 ) );
+
+let second = reader.next( ).unwrap( ).unwrap( );
+assert_eq!( second.loc.as_str( ), "https://example.org/blog/second-post" );
+
+// Outside of /blog/ so not permitted in this sitemap
+assert!( reader.next( ).unwrap( ).is_err( ) );
+assert!( reader.next( ).is_none( ) );
+# Ok( () )
+# }
+# run( );
+```
+ */
+
+use crate::{ BaseUrl, Url, ParseError };
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use std::io::{ self, BufRead };
+
+/// Errors encountered while reading or writing a sitemap
+#[derive(Debug)]
+pub enum SitemapError {
+    /// Reading from the underlying source failed
+    Io( io::Error ),
+    /// The document is not well formed XML. Holds the byte offset of the problem and a description
+    Xml( u64, String ),
+    /// The root element was neither `<urlset>` nor `<sitemapindex>`. Holds the name found
+    UnknownRoot( String ),
+    /// The input is gzip compressed but the `gzip` feature is disabled
+    Compressed,
+    /// An entry had no `<loc>` element
+    MissingLoc,
+    /// An entry's `<loc>` could not be parsed. Holds the text of the element and the parser's error
+    InvalidLoc( String, ParseError ),
+    /// An entry's `<loc>` lies outside of the scope of the sitemap
+    OutOfScope( Url ),
+    /// An entry is too large to fit in a sitemap on its own
+    TooLarge( Url ),
+    /// An entry would start a sitemap which no longer fits in the index, as the index already lists
+    /// 50,000 sitemaps or would exceed 50MB
+    IndexFull( Url ),
+}
+
+impl From< io::Error > for SitemapError {
+    fn from( err:io::Error ) -> Self {
+        SitemapError::Io( err )
+    }
+}
+
+/// The most Urls a single sitemap, or sitemaps a single index, may list
+pub const MAX_URLS:usize = 50_000;
+
+/// The largest a single uncompressed sitemap may be, in bytes
+pub const MAX_BYTES:usize = 50 * 1024 * 1024;
+
+const XML_DECLARATION:&str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+const SITEMAP_NAMESPACE:&str = "http://www.sitemaps.org/schemas/sitemap/0.9";
+
+/// Which kind of document is being read
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SitemapKind {
+    /// A `<urlset>` listing pages
+    UrlSet,
+    /// A `<sitemapindex>` listing other sitemaps
+    Index,
+}
+
+/// The `<changefreq>` values defined by the protocol
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChangeFreq {
+    Always,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+    Never,
+}
+
+impl ChangeFreq {
+
+    /// Parse a changefreq value, ignoring case and surrounding whitespace
+    pub fn parse( input:&str ) -> Option< Self > {
+        match input.trim( ).to_ascii_lowercase( ).as_str( ) {
+            "always" => Some( ChangeFreq::Always ),
+            "hourly" => Some( ChangeFreq::Hourly ),
+            "daily" => Some( ChangeFreq::Daily ),
+            "weekly" => Some( ChangeFreq::Weekly ),
+            "monthly" => Some( ChangeFreq::Monthly ),
+            "yearly" => Some( ChangeFreq::Yearly ),
+            "never" => Some( ChangeFreq::Never ),
+            _ => None,
+        }
+    }
+
+    /// The value as it is written in a sitemap
+    pub fn as_str( &self ) -> &'static str {
+        match *self {
+            ChangeFreq::Always => "always",
+            ChangeFreq::Hourly => "hourly",
+            ChangeFreq::Daily => "daily",
+            ChangeFreq::Weekly => "weekly",
+            ChangeFreq::Monthly => "monthly",
+            ChangeFreq::Yearly => "yearly",
+            ChangeFreq::Never => "never",
+        }
+    }
+}
+
+/// A single `<url>` of a urlset or `<sitemap>` of a sitemap index
+///
+/// Sitemap index entries only ever carry a `loc` and `lastmod`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SitemapEntry {
+    /// The location, resolved against the sitemap's BaseUrl
+    pub loc: Url,
+    /// The W3C Datetime the resource was last modified, exactly as written
+    pub lastmod: Option< String >,
+    /// How frequently the resource is likely to change. Unrecognised values are dropped
+    pub changefreq: Option< ChangeFreq >,
+    /// The priority relative to other Urls on the site. Values outside of 0.0 to 1.0 are dropped
+    pub priority: Option< f32 >,
+}
+
+/// Returns true if the sitemap found at `base` may list `url`
+///
+/// The Url must have the same scheme, host and port as the sitemap and a path beginning with the
+/// sitemap's directory.
+///
+/// # Examples
+///
+/// ```rust
+/// use base_url::{ BaseUrl, BaseUrlError, Url, TryFrom };
+/// use base_url::sitemap;
+///
+///# fn run( ) -> Result< (), BaseUrlError > {
+/// let base = BaseUrl::try_from( "http://example.org/catalog/sitemap.xml" )?;
+///
+/// assert!( sitemap::in_scope( &base, &Url::parse( "http://example.org/catalog/show?item=23" )? ) );
+/// assert!( !sitemap::in_scope( &base, &Url::parse( "http://example.org/images/" )? ) );
+/// assert!( !sitemap::in_scope( &base, &Url::parse( "https://example.org/catalog/" )? ) );
+/// assert!( !sitemap::in_scope( &base, &Url::parse( "http://www.example.org/catalog/" )? ) );
+///# Ok( () )
+///# }
+///# run( );
+/// ```
+pub fn in_scope( base:&BaseUrl, url:&Url ) -> bool {
+    let directory = match base.path( ).rfind( '/' ) {
+        Some( i ) => &base.path( )[..=i],
+        None => "/",
+    };
+    url.scheme( ) == base.scheme( )
+        && url.host_str( ) == Some( base.host_str( ) )
+        && url.port_or_known_default( ) == base.port_or_known_default( )
+        && url.path( ).starts_with( directory )
+}
+
+/// Returns true if the sitemap index found at `base` may list `url`. Index files may point at
+/// sitemaps anywhere on the same scheme, host and port.
+fn in_site( base:&BaseUrl, url:&Url ) -> bool {
+    url.scheme( ) == base.scheme( )
+        && url.host_str( ) == Some( base.host_str( ) )
+        && url.port_or_known_default( ) == base.port_or_known_default( )
+}
+
+/// The child element of an entry whose text is currently being collected
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Loc,
+    LastMod,
+    ChangeFreq,
+    Priority,
+}
+
+#[derive(Default)]
+struct PartialEntry {
+    loc: Option< String >,
+    lastmod: Option< String >,
+    changefreq: Option< String >,
+    priority: Option< String >,
+}
+
+/// A streaming reader over the entries of a sitemap or sitemap index
+///
+/// Iterating yields one `Result` per entry. Entry level problems such as an out of scope `<loc>` are
+/// reported without ending iteration, an I/O or XML error ends it.
+pub struct SitemapReader<'a> {
+    reader: Reader< Box< dyn BufRead + 'a > >,
+    base: BaseUrl,
+    kind: SitemapKind,
+    check_scope: bool,
+    buf: Vec< u8 >,
+    done: bool,
+}
+
+impl<'a> SitemapReader<'a> {
+
+    /// Begin reading a sitemap fetched from `base`. The document is read up to its root element to
+    /// determine whether it is a urlset or a sitemap index.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::sitemap::{ SitemapReader, SitemapKind };
+    /// use std::io::Write;
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let base = BaseUrl::try_from( "https://example.org/sitemap_index.xml.gz" )?;
+    /// let xml = "<sitemapindex><sitemap><loc>/sitemaps/1.xml</loc><lastmod>2004-10-01</lastmod></sitemap></sitemapindex>";
+    ///
+    /// #[cfg(feature = "gzip")]
+    /// let input = {
+    ///     let mut gz = flate2::write::GzEncoder::new( Vec::new( ), flate2::Compression::default( ) );
+    ///     gz.write_all( xml.as_bytes( ) ).unwrap( );
+    ///     gz.finish( ).unwrap( )
+    /// };
+    /// #[cfg(not(feature = "gzip"))]
+    /// let input = xml.as_bytes( ).to_vec( );
+    ///
+    /// let reader = SitemapReader::new( &input[..], &base ).unwrap( );
+    /// assert_eq!( reader.kind( ), SitemapKind::Index );
+    ///
+    /// let sitemaps:Vec<_> = reader.map( |entry| entry.unwrap( ).loc.into_string( ) ).collect( );
+    /// assert_eq!( sitemaps, vec![ "https://example.org/sitemaps/1.xml" ] );
+    ///
+    /// // Prefixed names are read by their local name
+    /// let xml = r#"<sm:urlset xmlns:sm="http://www.sitemaps.org/schemas/sitemap/0.9"><sm:url><sm:loc>/a</sm:loc></sm:url></sm:urlset>"#;
+    /// let reader = SitemapReader::new( xml.as_bytes( ), &base ).unwrap( );
+    /// let urls:Vec<_> = reader.map( |entry| entry.unwrap( ).loc.into_string( ) ).collect( );
+    /// assert_eq!( urls, vec![ "https://example.org/a" ] );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn new< R: BufRead + 'a >( mut input:R, base:&BaseUrl ) -> Result< Self, SitemapError > {
+        let gzipped = input.fill_buf( )?.starts_with( &[ 0x1f, 0x8b ] );
+        let input:Box< dyn BufRead + 'a > = if gzipped {
+            gunzip( input )?
+        } else {
+            Box::new( input )
+        };
+
+        let mut reader = Reader::from_reader( input );
+        reader.config_mut( ).trim_text( true );
+
+        let mut buf = Vec::new( );
+        let kind = loop {
+            let event = reader.read_event_into( &mut buf )
+                              .map_err( |e| SitemapError::Xml( reader.error_position( ), e.to_string( ) ) )?;
+            match event {
+                Event::Start( e ) | Event::Empty( e ) => {
+                    match e.local_name( ).as_ref( ) {
+                        b"urlset" => break SitemapKind::UrlSet,
+                        b"sitemapindex" => break SitemapKind::Index,
+                        other => return Err( SitemapError::UnknownRoot( String::from_utf8_lossy( other ).into_owned( ) ) ),
+                    }
+                }
+                Event::Eof => return Err( SitemapError::UnknownRoot( String::new( ) ) ),
+                _ => {}
+            }
+            buf.clear( );
+        };
+
+        Ok( SitemapReader{ reader, base: base.clone( ), kind, check_scope: true, buf, done: false } )
+    }
+
+    /// Whether the document is a urlset or a sitemap index
+    pub fn kind( &self ) -> SitemapKind {
+        self.kind
+    }
+
+    /// Stop checking entries against the scope of the sitemap.
+    ///
+    /// The protocol allows a sitemap to list Urls on other hosts when it has been submitted through
+    /// a robots.txt file on those hosts, something only the caller can know.
+    pub fn without_scope_check( mut self ) -> Self {
+        self.check_scope = false;
+        self
+    }
+
+    fn xml_error( &mut self, err:quick_xml::Error ) -> SitemapError {
+        self.done = true;
+        SitemapError::Xml( self.reader.error_position( ), err.to_string( ) )
+    }
+
+    /// Read up to the end of the next entry
+    fn read_entry( &mut self ) -> Option< Result< PartialEntry, SitemapError > > {
+        let entry_tag:&[u8] = match self.kind {
+            SitemapKind::UrlSet => b"url",
+            SitemapKind::Index => b"sitemap",
+        };
+        let mut entry:Option< PartialEntry > = None;
+        let mut field:Option< Field > = None;
+        // Depth of nesting below the root element
+        let mut depth = 0usize;
+
+        loop {
+            self.buf.clear( );
+            let event = match self.reader.read_event_into( &mut self.buf ) {
+                Ok( event ) => event,
+                Err( e ) => return Some( Err( self.xml_error( e ) ) ),
+            };
+            match event {
+                Event::Start( e ) => {
+                    depth += 1;
+                    // Namespace prefixes are ignored, as they are for the root. Extension elements
+                    // such as <image:loc> are nested a level deeper and so never match here
+                    let name = e.local_name( );
+                    if depth == 1 && name.as_ref( ) == entry_tag {
+                        entry = Some( PartialEntry::default( ) );
+                    } else if depth == 2 && entry.is_some( ) {
+                        field = match name.as_ref( ) {
+                            b"loc" => Some( Field::Loc ),
+                            b"lastmod" => Some( Field::LastMod ),
+                            b"changefreq" => Some( Field::ChangeFreq ),
+                            b"priority" => Some( Field::Priority ),
+                            _ => None,
+                        };
+                    }
+                }
+                Event::Text( t ) => {
+                    if let ( Some( f ), Some( entry ) ) = ( field, entry.as_mut( ) ) {
+                        match t.unescape( ) {
+                            Ok( text ) => push_text( entry, f, &text ),
+                            Err( e ) => return Some( Err( self.xml_error( e ) ) ),
+                        }
+                    }
+                }
+                Event::CData( t ) => {
+                    if let ( Some( f ), Some( entry ) ) = ( field, entry.as_mut( ) ) {
+                        push_text( entry, f, &String::from_utf8_lossy( &t ) );
+                    }
+                }
+                Event::End( _ ) => {
+                    if depth == 0 {
+                        // The root element has closed
+                        self.done = true;
+                        return None;
+                    }
+                    depth -= 1;
+                    field = None;
+                    if depth == 0 {
+                        if let Some( entry ) = entry.take( ) {
+                            return Some( Ok( entry ) );
+                        }
+                    }
+                }
+                Event::Eof => {
+                    self.done = true;
+                    return None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn finish_entry( &self, partial:PartialEntry ) -> Result< SitemapEntry, SitemapError > {
+        let loc_text = partial.loc.ok_or( SitemapError::MissingLoc )?;
+        let loc = self.base.url.join( loc_text.trim( ) )
+                           .map_err( |e| SitemapError::InvalidLoc( loc_text.clone( ), e ) )?;
+
+        if self.check_scope {
+            let permitted = match self.kind {
+                SitemapKind::UrlSet => in_scope( &self.base, &loc ),
+                SitemapKind::Index => in_site( &self.base, &loc ),
+            };
+            if !permitted {
+                return Err( SitemapError::OutOfScope( loc ) );
+            }
+        }
+
+        Ok( SitemapEntry{
+            loc,
+            lastmod: partial.lastmod.map( |s| s.trim( ).to_owned( ) ),
+            changefreq: partial.changefreq.as_ref( ).and_then( |s| ChangeFreq::parse( s ) ),
+            priority: partial.priority.and_then( |s| s.trim( ).parse( ).ok( ) )
+                                      .filter( |p:&f32| ( 0.0..=1.0 ).contains( p ) ),
+        } )
+    }
+}
+
+impl<'a> Iterator for SitemapReader<'a> {
+    type Item = Result< SitemapEntry, SitemapError >;
+
+    fn next( &mut self ) -> Option< Self::Item > {
+        if self.done {
+            return None;
+        }
+        match self.read_entry( )? {
+            Ok( partial ) => Some( self.finish_entry( partial ) ),
+            Err( e ) => Some( Err( e ) ),
+        }
+    }
+}
+
+fn push_text( entry:&mut PartialEntry, field:Field, text:&str ) {
+    let slot = match field {
+        Field::Loc => &mut entry.loc,
+        Field::LastMod => &mut entry.lastmod,
+        Field::ChangeFreq => &mut entry.changefreq,
+        Field::Priority => &mut entry.priority,
+    };
+    slot.get_or_insert_with( String::new ).push_str( text );
+}
+
+#[cfg(feature = "gzip")]
+fn gunzip<'a, R: BufRead + 'a>( input:R ) -> Result< Box< dyn BufRead + 'a >, SitemapError > {
+    Ok( Box::new( io::BufReader::new( flate2::bufread::MultiGzDecoder::new( input ) ) ) )
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gunzip<'a, R: BufRead + 'a>( _input:R ) -> Result< Box< dyn BufRead + 'a >, SitemapError > {
+    Err( SitemapError::Compressed )
+}
+
+/// A generated sitemap or sitemap index along with the location it must be published at
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SitemapFile {
+    /// Where the file must be served from for the Urls it lists to be in scope
+    pub location: BaseUrl,
+    /// The XML document
+    pub contents: String,
+}
+
+/// The output of a SitemapWriter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SitemapSet {
+    /// The numbered sitemaps, in order
+    pub sitemaps: Vec< SitemapFile >,
+    /// The sitemap index listing every sitemap
+    pub index: SitemapFile,
+}
+
+/// Builds the sitemaps for every page under a BaseUrl
+///
+/// Urls are added one at a time and a new numbered sitemap is started whenever the current one would
+/// exceed 50,000 Urls or 50MB. The sitemaps are named `sitemap_1.xml`, `sitemap_2.xml` and so on and
+/// are placed, along with `sitemap_index.xml`, in the directory of the BaseUrl the writer was created
+/// with. Only Urls in the scope of that directory are accepted.
+///
+/// # Examples
+///
+/// ```rust
+/// use base_url::{ BaseUrl, BaseUrlError, Url, TryFrom };
+/// use base_url::sitemap::SitemapWriter;
+///
+///# fn run( ) -> Result< (), BaseUrlError > {
+/// let base = BaseUrl::try_from( "https://example.org/docs/" )?;
+/// let mut writer = SitemapWriter::new( &base ).max_urls( 2 );
+///
+/// writer.add( &Url::parse( "https://example.org/docs/intro" )? ).unwrap( );
+/// writer.add( &Url::parse( "https://example.org/docs/search?q=a&b=<c>" )? ).unwrap( );
+/// writer.add( &Url::parse( "https://example.org/docs/faq" )? ).unwrap( );
+/// assert!( writer.add( &Url::parse( "https://example.org/blog/" )? ).is_err( ) );
+///
+/// let set = writer.finish( );
+/// assert_eq!( set.sitemaps.len( ), 2 );
+/// assert_eq!( set.sitemaps[1].location.as_str( ), "https://example.org/docs/sitemap_2.xml" );
+/// assert!( set.sitemaps[0].contents.contains( "<loc>https://example.org/docs/search?q=a&amp;b=%3Cc%3E</loc>" ) );
+///
+/// assert_eq!( set.index.location.as_str( ), "https://example.org/docs/sitemap_index.xml" );
+/// assert!( set.index.contents.contains( "<loc>https://example.org/docs/sitemap_1.xml</loc>" ) );
+///# Ok( () )
+///# }
+///# run( );
+/// ```
+pub struct SitemapWriter {
+    base: BaseUrl,
+    max_urls: usize,
+    max_bytes: usize,
+    finished: Vec< SitemapFile >,
+    index_len: usize,
+    current: String,
+    current_urls: usize,
+}
+
+impl SitemapWriter {
+
+    /// Create a writer for the pages in the directory of `base`
+    pub fn new( base:&BaseUrl ) -> Self {
+        SitemapWriter{
+            base: base.clone( ),
+            max_urls: MAX_URLS,
+            max_bytes: MAX_BYTES,
+            finished: Vec::new( ),
+            index_len: open_tag( "sitemapindex" ).len( ) + "</sitemapindex>\n".len( ),
+            current: String::new( ),
+            current_urls: 0,
+        }
+    }
+
+    /// Lower the number of Urls written to each sitemap. Values above the protocol's limit of 50,000
+    /// are ignored.
+    pub fn max_urls( mut self, max:usize ) -> Self {
+        self.max_urls = max.clamp( 1, MAX_URLS );
+        self
+    }
+
+    /// Lower the size in bytes of each sitemap. Values above the protocol's limit of 50MB are ignored.
+    pub fn max_bytes( mut self, max:usize ) -> Self {
+        self.max_bytes = max.min( MAX_BYTES );
+        self
+    }
+
+    /// Add a Url with no further information
+    ///
+    /// # Errors
+    ///
+    /// If the Url lies outside of the writer's scope SitemapError::OutOfScope is returned and nothing
+    /// is written.
+    pub fn add( &mut self, url:&Url ) -> Result< (), SitemapError > {
+        self.add_entry( &SitemapEntry{ loc: url.clone( ), lastmod: None, changefreq: None, priority: None } )
+    }
+
+    /// Add a Url along with its lastmod, changefreq and priority
+    ///
+    /// # Errors
+    ///
+    /// If the entry's Url lies outside of the writer's scope SitemapError::OutOfScope is returned. If
+    /// the entry alone would exceed the configured size of a sitemap SitemapError::TooLarge is
+    /// returned. If the entry needs a new sitemap and the index cannot list another
+    /// SitemapError::IndexFull is returned. In each case nothing is written.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, Url, TryFrom };
+    /// use base_url::sitemap::{ SitemapError, SitemapWriter, MAX_URLS };
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let base = BaseUrl::try_from( "https://example.org/" )?;
+    /// let mut writer = SitemapWriter::new( &base ).max_urls( 1 );
+    /// for i in 0..MAX_URLS {
+    ///     writer.add( &Url::parse( &format!( "https://example.org/{}", i ) )? ).unwrap( );
+    /// }
+    ///
+    /// match writer.add( &Url::parse( "https://example.org/one-too-many" )? ) {
+    ///     Err( SitemapError::IndexFull( url ) ) => assert_eq!( url.path( ), "/one-too-many" ),
+    ///     other => panic!( "{:?}", other ),
+    /// }
+    /// assert_eq!( writer.finish( ).sitemaps.len( ), MAX_URLS );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn add_entry( &mut self, entry:&SitemapEntry ) -> Result< (), SitemapError > {
+        if !in_scope( &self.base, &entry.loc ) {
+            return Err( SitemapError::OutOfScope( entry.loc.clone( ) ) );
+        }
+
+        let mut element = String::from( "  <url>\n    <loc>" );
+        element.push_str( &escape( entry.loc.as_str( ) ) );
+        element.push_str( "</loc>\n" );
+        if let Some( ref lastmod ) = entry.lastmod {
+            element.push_str( &format!( "    <lastmod>{}</lastmod>\n", escape( lastmod ) ) );
+        }
+        if let Some( changefreq ) = entry.changefreq {
+            element.push_str( &format!( "    <changefreq>{}</changefreq>\n", changefreq.as_str( ) ) );
+        }
+        if let Some( priority ) = entry.priority {
+            element.push_str( &format!( "    <priority>{}</priority>\n", priority.clamp( 0.0, 1.0 ) ) );
+        }
+        element.push_str( "  </url>\n" );
+
+        let footer = "</urlset>\n";
+        if open_tag( "urlset" ).len( ) + element.len( ) + footer.len( ) > self.max_bytes {
+            return Err( SitemapError::TooLarge( entry.loc.clone( ) ) );
+        }
+        let full = self.current_urls == self.max_urls
+            || self.current.len( ) + element.len( ) + footer.len( ) > self.max_bytes;
+        if full || self.current.is_empty( ) {
+            // The index must have room for the sitemap being closed and the one this entry starts
+            let first = self.finished.len( ) + 1;
+            let last = if full { first + 1 } else { first };
+            let added:usize = ( first..=last ).map( |n| self.index_entry( n ).len( ) ).sum( );
+            if last > MAX_URLS || self.index_len + added > MAX_BYTES {
+                return Err( SitemapError::IndexFull( entry.loc.clone( ) ) );
+            }
+        }
+        if full {
+            self.close_current( );
+        }
+        if self.current.is_empty( ) {
+            self.current.push_str( &open_tag( "urlset" ) );
+        }
+        self.current.push_str( &element );
+        self.current_urls += 1;
+        Ok( ( ) )
+    }
+
+    /// Finish the last sitemap and write the index. An index is always produced, even when every Url
+    /// fit in a single sitemap.
+    pub fn finish( mut self ) -> SitemapSet {
+        if self.current_urls > 0 || self.finished.is_empty( ) {
+            self.close_current( );
+        }
+
+        let mut index = open_tag( "sitemapindex" );
+        for n in 1..=self.finished.len( ) {
+            index.push_str( &self.index_entry( n ) );
+        }
+        index.push_str( "</sitemapindex>\n" );
+
+        let location = self.file_location( "sitemap_index.xml" );
+        SitemapSet{
+            sitemaps: self.finished,
+            index: SitemapFile{ location, contents: index },
+        }
+    }
+
+    fn close_current( &mut self ) {
+        let mut contents = std::mem::take( &mut self.current );
+        if contents.is_empty( ) {
+            contents.push_str( &open_tag( "urlset" ) );
+        }
+        contents.push_str( "</urlset>\n" );
+        let name = format!( "sitemap_{}.xml", self.finished.len( ) + 1 );
+        self.index_len += self.index_entry( self.finished.len( ) + 1 ).len( );
+        self.finished.push( SitemapFile{ location: self.file_location( &name ), contents } );
+        self.current_urls = 0;
+    }
+
+    /// The element listing sitemap number `n` in the index
+    fn index_entry( &self, n:usize ) -> String {
+        let location = self.file_location( &format!( "sitemap_{}.xml", n ) );
+        format!( "  <sitemap>\n    <loc>{}</loc>\n  </sitemap>\n", escape( location.as_str( ) ) )
+    }
+
+    /// The location of a file named `name` in the writer's directory
+    fn file_location( &self, name:&str ) -> BaseUrl {
+        let mut location = self.base.clone( );
+        location.strip( );
+        location.path_segments_mut( ).pop( ).push( name );
+        location
+    }
+}
+
+fn open_tag( root:&str ) -> String {
+    format!( "{}<{} xmlns=\"{}\">\n", XML_DECLARATION, root, SITEMAP_NAMESPACE )
+}
+
+/// Escape the five characters the protocol requires to be written as entities
+fn escape( input:&str ) -> String {
+    let mut out = String::with_capacity( input.len( ) );
+    for c in input.chars( ) {
+        match c {
+            '&' => out.push_str( "&amp;" ),
+            '<' => out.push_str( "&lt;" ),
+            '>' => out.push_str( "&gt;" ),
+            '\'' => out.push_str( "&apos;" ),
+            '"' => out.push_str( "&quot;" ),
+            _ => out.push( c ),
+        }
+    }
+    out
+}
@@ -0,0 +1,742 @@
+/*!
+Writing serde types to, and reading them from, the query of a BaseUrl, enabled by the `serde` feature.
+
+The fields of a struct or entries of a map become query parameters. Nested structs and maps are
+written with their keys joined in the notation chosen by QueryOptions, either `a[b][c]` or `a.b.c`.
+Sequences of plain values are written either as a repeated key or as one comma separated value, and
+sequences of structs as indexed keys such as `a[0][b]`. An empty sequence is written as its key alone,
+as in `tags`, so that it reads back as empty. `None` values are left out, and when reading an empty
+value is read as `None`.
+
+Only brackets and dots written as they are nest keys, while percent-encoded ones are part of a key's
+name. A map key such as `a[b]` is written as `a%5Bb%5D` and so reads back as the same single key.
+ */
+
+use serde::de::{ self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor };
+use serde::ser::{ self, Serialize };
+use url::form_urlencoded::byte_serialize;
+use url::percent_encoding::percent_decode;
+
+use std::error::Error;
+use std::fmt::{ Display, Formatter, Result as FormatResult };
+use std::vec;
+
+/// How the keys of nested structs and maps are joined
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Nesting {
+    /// `filter[price][min]=10`
+    #[default]
+    Brackets,
+    /// `filter.price.min=10`
+    Dots,
+}
+
+/// How sequences of plain values are written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Sequences {
+    /// `tag=a&tag=b`
+    #[default]
+    Repeated,
+    /// `tag=a,b`, with any commas within the values percent-encoded
+    Comma,
+}
+
+/// How serde types are laid out in a query
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct QueryOptions {
+    pub nesting: Nesting,
+    pub sequences: Sequences,
+}
+
+/// An error writing a value to or reading it from a query
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryError {
+    key: String,
+    message: String,
+    /// The segments of the key, innermost first, gathered as the error is returned through them
+    path: Vec< String >,
+}
+
+impl QueryError {
+
+    fn new< M:Display >( message:M ) -> Self {
+        QueryError{ key: String::new( ), message: message.to_string( ), path: Vec::new( ) }
+    }
+
+    /// Add the segment of the key the error was returned through
+    fn within( mut self, segment:&str ) -> Self {
+        self.path.push( segment.to_owned( ) );
+        self
+    }
+
+    /// Render the gathered key in the notation of the options
+    fn locate( mut self, options:&QueryOptions ) -> Self {
+        self.path.reverse( );
+        self.key = render_key( &self.path, options.nesting, false );
+        self
+    }
+
+    /// The key of the offending parameter, written in the notation of the QueryOptions in use. Empty
+    /// if the error concerns the query as a whole.
+    pub fn key( &self ) -> &str {
+        &self.key
+    }
+
+    /// A description of the problem
+    pub fn message( &self ) -> &str {
+        &self.message
+    }
+}
+
+impl Display for QueryError {
+    fn fmt( &self, formatter:&mut Formatter ) -> FormatResult {
+        if self.key.is_empty( ) {
+            formatter.write_str( &self.message )
+        } else {
+            write!( formatter, "`{}`: {}", self.key, self.message )
+        }
+    }
+}
+
+impl Error for QueryError {}
+
+impl ser::Error for QueryError {
+    fn custom< T:Display >( message:T ) -> Self {
+        QueryError::new( message )
+    }
+}
+
+impl de::Error for QueryError {
+    fn custom< T:Display >( message:T ) -> Self {
+        QueryError::new( message )
+    }
+
+    fn missing_field( field:&'static str ) -> Self {
+        QueryError::new( "missing field" ).within( field )
+    }
+}
+
+/// Serialize a value into a query, without a leading `?`
+pub(crate) fn to_query< T:Serialize + ?Sized >( value:&T, options:&QueryOptions ) -> Result< String, QueryError > {
+    let node = value.serialize( NodeSerializer ).map_err( |e| e.locate( options ) )?;
+    let mut pairs = Vec::new( );
+    match node {
+        Node::Absent => {}
+        Node::Map( _ ) => render( &mut Vec::new( ), &node, options, &mut pairs ),
+        _ => return Err( QueryError::new( "only structs and maps can be written as a query" ) ),
+    }
+    Ok( pairs.join( "&" ) )
+}
+
+/// Deserialize a value from a query, without its leading `?`
+pub(crate) fn from_query< T:DeserializeOwned >( query:&str, options:&QueryOptions ) -> Result< T, QueryError > {
+    let mut root = Vec::new( );
+    for pair in query.split( '&' ).filter( |p| !p.is_empty( ) ) {
+        let ( key, value ) = match pair.find( '=' ) {
+            Some( i ) => ( &pair[..i], Value::Raw( pair[i + 1..].to_owned( ) ) ),
+            None => ( pair, Value::Bare ),
+        };
+        let path = parse_key( key, options.nesting );
+        if insert( &mut root, &path, value ).is_err( ) {
+            let mut err = QueryError::new( "the key is given both a value and nested keys" );
+            err.path = path.into_iter( ).rev( ).collect( );
+            return Err( err.locate( options ) );
+        }
+    }
+    T::deserialize( ValueDeserializer{ value: Value::Map( root ), options } ).map_err( |e| e.locate( options ) )
+}
+
+fn decode( input:&str ) -> String {
+    let input = input.replace( '+', " " );
+    percent_decode( input.as_bytes( ) ).decode_utf8_lossy( ).into_owned( )
+}
+
+fn encode( input:&str ) -> String {
+    byte_serialize( input.as_bytes( ) ).collect( )
+}
+
+/// Join the segments of a key, percent-encoding them if it is to be written to a query. Brackets are
+/// always percent-encoded within segments, and so are dots when they nest keys.
+fn render_key( path:&[String], nesting:Nesting, encoded:bool ) -> String {
+    let mut key = String::new( );
+    for ( i, segment ) in path.iter( ).enumerate( ) {
+        let segment = match nesting {
+            _ if !encoded => segment.clone( ),
+            Nesting::Brackets => encode( segment ),
+            Nesting::Dots => encode( segment ).replace( '.', "%2E" ),
+        };
+        match nesting {
+            _ if i == 0 => key.push_str( &segment ),
+            Nesting::Brackets => {
+                key.push( '[' );
+                key.push_str( &segment );
+                key.push( ']' );
+            }
+            Nesting::Dots => {
+                key.push( '.' );
+                key.push_str( &segment );
+            }
+        }
+    }
+    key
+}
+
+/// Split a still percent-encoded key into its decoded segments. Empty segments, as in `tag[]`, are
+/// dropped.
+fn parse_key( key:&str, nesting:Nesting ) -> Vec< String > {
+    let segments:Vec< &str > = match nesting {
+        Nesting::Dots => key.split( '.' ).collect( ),
+        Nesting::Brackets => {
+            let first = key.find( '[' ).unwrap_or( key.len( ) );
+            let mut segments = vec![ &key[..first] ];
+            let mut rest = &key[first..];
+            while let Some( inner ) = rest.strip_prefix( '[' ) {
+                match inner.find( ']' ) {
+                    Some( end ) => {
+                        segments.push( &inner[..end] );
+                        rest = &inner[end + 1..];
+                    }
+                    None => break,
+                }
+            }
+            if !rest.is_empty( ) {
+                // Not well formed, so the key is taken as it is
+                segments = vec![ key ];
+            }
+            segments
+        }
+    };
+    let mut path:Vec< String > = segments.into_iter( ).filter( |s| !s.is_empty( ) ).map( decode ).collect( );
+    if path.is_empty( ) {
+        path.push( String::new( ) );
+    }
+    path
+}
+
+/// A serialized value
+enum Node {
+    Absent,
+    Scalar( String ),
+    Seq( Vec< Node > ),
+    Map( Vec< ( String, Node ) > ),
+}
+
+/// Write a serialized value as `key=value` pairs
+fn render( path:&mut Vec< String >, node:&Node, options:&QueryOptions, pairs:&mut Vec< String > ) {
+    match node {
+        Node::Absent => {}
+        Node::Scalar( value ) => pairs.push( format!( "{}={}", render_key( path, options.nesting, true ), encode( value ) ) ),
+        Node::Seq( items ) if items.iter( ).all( |i| matches!( i, Node::Scalar( _ ) | Node::Absent ) ) => {
+            let values = items.iter( ).filter_map( |i| match i {
+                Node::Scalar( value ) => Some( encode( value ) ),
+                _ => None,
+            } );
+            let key = render_key( path, options.nesting, true );
+            let values:Vec< String > = values.collect( );
+            match options.sequences {
+                _ if values.is_empty( ) => pairs.push( key ),
+                Sequences::Repeated => pairs.extend( values.iter( ).map( |v| format!( "{}={}", key, v ) ) ),
+                Sequences::Comma => pairs.push( format!( "{}={}", key, values.join( "," ) ) ),
+            }
+        }
+        Node::Seq( items ) => {
+            for ( i, item ) in items.iter( ).enumerate( ) {
+                path.push( i.to_string( ) );
+                render( path, item, options, pairs );
+                path.pop( );
+            }
+        }
+        Node::Map( entries ) => {
+            for ( key, value ) in entries {
+                path.push( key.clone( ) );
+                render( path, value, options, pairs );
+                path.pop( );
+            }
+        }
+    }
+}
+
+struct NodeSerializer;
+
+macro_rules! serialize_display {
+    ( $( $method:ident( $t:ty ) ),* ) => {
+        $(
+            fn $method( self, value:$t ) -> Result< Node, QueryError > {
+                Ok( Node::Scalar( value.to_string( ) ) )
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for NodeSerializer {
+    type Ok = Node;
+    type Error = QueryError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = ser::Impossible< Node, QueryError >;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = ser::Impossible< Node, QueryError >;
+
+    serialize_display!( serialize_bool( bool ), serialize_char( char ), serialize_str( &str ),
+                        serialize_i8( i8 ), serialize_i16( i16 ), serialize_i32( i32 ), serialize_i64( i64 ),
+                        serialize_u8( u8 ), serialize_u16( u16 ), serialize_u32( u32 ), serialize_u64( u64 ),
+                        serialize_f32( f32 ), serialize_f64( f64 ) );
+
+    fn serialize_bytes( self, _:&[u8] ) -> Result< Node, QueryError > {
+        Err( QueryError::new( "bytes cannot be written to a query" ) )
+    }
+
+    fn serialize_none( self ) -> Result< Node, QueryError > {
+        Ok( Node::Absent )
+    }
+
+    fn serialize_some< T:Serialize + ?Sized >( self, value:&T ) -> Result< Node, QueryError > {
+        value.serialize( self )
+    }
+
+    fn serialize_unit( self ) -> Result< Node, QueryError > {
+        Ok( Node::Absent )
+    }
+
+    fn serialize_unit_struct( self, _:&'static str ) -> Result< Node, QueryError > {
+        Ok( Node::Absent )
+    }
+
+    fn serialize_unit_variant( self, _:&'static str, _:u32, variant:&'static str ) -> Result< Node, QueryError > {
+        Ok( Node::Scalar( variant.to_owned( ) ) )
+    }
+
+    fn serialize_newtype_struct< T:Serialize + ?Sized >( self, _:&'static str, value:&T ) -> Result< Node, QueryError > {
+        value.serialize( self )
+    }
+
+    fn serialize_newtype_variant< T:Serialize + ?Sized >( self, _:&'static str, _:u32, variant:&'static str, value:&T ) -> Result< Node, QueryError > {
+        let value = value.serialize( NodeSerializer ).map_err( |e| e.within( variant ) )?;
+        Ok( Node::Map( vec![ ( variant.to_owned( ), value ) ] ) )
+    }
+
+    fn serialize_seq( self, len:Option< usize > ) -> Result< SeqSerializer, QueryError > {
+        Ok( SeqSerializer{ items: Vec::with_capacity( len.unwrap_or( 0 ) ) } )
+    }
+
+    fn serialize_tuple( self, len:usize ) -> Result< SeqSerializer, QueryError > {
+        self.serialize_seq( Some( len ) )
+    }
+
+    fn serialize_tuple_struct( self, _:&'static str, len:usize ) -> Result< SeqSerializer, QueryError > {
+        self.serialize_seq( Some( len ) )
+    }
+
+    fn serialize_tuple_variant( self, _:&'static str, _:u32, variant:&'static str, _:usize ) -> Result< Self::SerializeTupleVariant, QueryError > {
+        Err( QueryError::new( format!( "the tuple variant `{}` cannot be written to a query", variant ) ) )
+    }
+
+    fn serialize_map( self, _:Option< usize > ) -> Result< MapSerializer, QueryError > {
+        Ok( MapSerializer{ entries: Vec::new( ), key: None } )
+    }
+
+    fn serialize_struct( self, _:&'static str, _:usize ) -> Result< MapSerializer, QueryError > {
+        self.serialize_map( None )
+    }
+
+    fn serialize_struct_variant( self, _:&'static str, _:u32, variant:&'static str, _:usize ) -> Result< Self::SerializeStructVariant, QueryError > {
+        Err( QueryError::new( format!( "the struct variant `{}` cannot be written to a query", variant ) ) )
+    }
+}
+
+struct SeqSerializer {
+    items: Vec< Node >,
+}
+
+impl SeqSerializer {
+
+    fn push< T:Serialize + ?Sized >( &mut self, value:&T ) -> Result< (), QueryError > {
+        let index = self.items.len( ).to_string( );
+        self.items.push( value.serialize( NodeSerializer ).map_err( |e| e.within( &index ) )? );
+        Ok( () )
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Node;
+    type Error = QueryError;
+
+    fn serialize_element< T:Serialize + ?Sized >( &mut self, value:&T ) -> Result< (), QueryError > {
+        self.push( value )
+    }
+
+    fn end( self ) -> Result< Node, QueryError > {
+        Ok( Node::Seq( self.items ) )
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Node;
+    type Error = QueryError;
+
+    fn serialize_element< T:Serialize + ?Sized >( &mut self, value:&T ) -> Result< (), QueryError > {
+        self.push( value )
+    }
+
+    fn end( self ) -> Result< Node, QueryError > {
+        Ok( Node::Seq( self.items ) )
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Node;
+    type Error = QueryError;
+
+    fn serialize_field< T:Serialize + ?Sized >( &mut self, value:&T ) -> Result< (), QueryError > {
+        self.push( value )
+    }
+
+    fn end( self ) -> Result< Node, QueryError > {
+        Ok( Node::Seq( self.items ) )
+    }
+}
+
+struct MapSerializer {
+    entries: Vec< ( String, Node ) >,
+    key: Option< String >,
+}
+
+impl MapSerializer {
+
+    fn push< T:Serialize + ?Sized >( &mut self, key:String, value:&T ) -> Result< (), QueryError > {
+        let value = value.serialize( NodeSerializer ).map_err( |e| e.within( &key ) )?;
+        self.entries.push( ( key, value ) );
+        Ok( () )
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Node;
+    type Error = QueryError;
+
+    fn serialize_key< T:Serialize + ?Sized >( &mut self, key:&T ) -> Result< (), QueryError > {
+        match key.serialize( NodeSerializer )? {
+            Node::Scalar( key ) => {
+                self.key = Some( key );
+                Ok( () )
+            }
+            _ => Err( QueryError::new( "map keys must be strings or numbers" ) ),
+        }
+    }
+
+    fn serialize_value< T:Serialize + ?Sized >( &mut self, value:&T ) -> Result< (), QueryError > {
+        let key = self.key.take( ).unwrap_or_default( );
+        self.push( key, value )
+    }
+
+    fn end( self ) -> Result< Node, QueryError > {
+        Ok( Node::Map( self.entries ) )
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Node;
+    type Error = QueryError;
+
+    fn serialize_field< T:Serialize + ?Sized >( &mut self, key:&'static str, value:&T ) -> Result< (), QueryError > {
+        self.push( key.to_owned( ), value )
+    }
+
+    fn end( self ) -> Result< Node, QueryError > {
+        Ok( Node::Map( self.entries ) )
+    }
+}
+
+/// A parsed query value, before it is deserialized
+enum Value {
+    /// A still percent-encoded value
+    Raw( String ),
+    /// A key given without `=`, which is an empty value or an empty sequence
+    Bare,
+    /// The values of a repeated key
+    List( Vec< Value > ),
+    Map( Vec< ( String, Value ) > ),
+}
+
+/// Add a value to the tree at the given path, failing if a key would hold both a value and nested
+/// keys
+fn insert( entries:&mut Vec< ( String, Value ) >, path:&[String], value:Value ) -> Result< (), () > {
+    let ( first, rest ) = path.split_first( ).ok_or( ( ) )?;
+    let existing = entries.iter( ).position( |( k, _ )| k == first );
+    if rest.is_empty( ) {
+        match existing {
+            None => entries.push( ( first.clone( ), value ) ),
+            Some( i ) => match &mut entries[i].1 {
+                Value::List( items ) => items.push( value ),
+                Value::Map( _ ) => return Err( () ),
+                raw => {
+                    let previous = std::mem::replace( raw, Value::List( Vec::new( ) ) );
+                    *raw = Value::List( vec![ previous, value ] );
+                }
+            },
+        }
+        return Ok( () );
+    }
+    let i = match existing {
+        Some( i ) => i,
+        None => {
+            entries.push( ( first.clone( ), Value::Map( Vec::new( ) ) ) );
+            entries.len( ) - 1
+        }
+    };
+    match &mut entries[i].1 {
+        Value::Map( children ) => insert( children, rest, value ),
+        _ => Err( () ),
+    }
+}
+
+struct ValueDeserializer<'o> {
+    value: Value,
+    options: &'o QueryOptions,
+}
+
+impl<'o> ValueDeserializer<'o> {
+
+    /// The decoded value, which must be a single one
+    fn scalar( self ) -> Result< String, QueryError > {
+        match self.value {
+            Value::Raw( raw ) => Ok( decode( &raw ) ),
+            Value::Bare => Ok( String::new( ) ),
+            Value::List( items ) => Err( QueryError::new( format!( "expected a single value, found {}", items.len( ) ) ) ),
+            Value::Map( _ ) => Err( QueryError::new( "expected a value, found nested keys" ) ),
+        }
+    }
+
+    /// The items of a sequence, from a repeated key, a comma separated value or indexed keys
+    fn items( self ) -> Result< Vec< Value >, QueryError > {
+        let comma = self.options.sequences == Sequences::Comma;
+        let split = |raw:String| -> Vec< Value > {
+            if comma {
+                raw.split( ',' ).map( |v| Value::Raw( v.to_owned( ) ) ).collect( )
+            } else {
+                vec![ Value::Raw( raw ) ]
+            }
+        };
+        match self.value {
+            Value::Raw( raw ) => Ok( split( raw ) ),
+            Value::Bare => Ok( Vec::new( ) ),
+            Value::List( items ) => Ok( items.into_iter( ).flat_map( |item| match item {
+                Value::Raw( raw ) => split( raw ),
+                Value::Bare => Vec::new( ),
+                other => vec![ other ],
+            } ).collect( ) ),
+            Value::Map( entries ) => {
+                let mut indexed = Vec::with_capacity( entries.len( ) );
+                for ( key, value ) in entries {
+                    match key.parse::< usize >( ) {
+                        Ok( index ) => indexed.push( ( index, value ) ),
+                        Err( _ ) => return Err( QueryError::new( format!( "expected a sequence, found the key `{}`", key ) ) ),
+                    }
+                }
+                indexed.sort_by_key( |( index, _ )| *index );
+                Ok( indexed.into_iter( ).map( |( _, value )| value ).collect( ) )
+            }
+        }
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ( $( $method:ident => $visit:ident ),* ) => {
+        $(
+            fn $method< V:Visitor<'de> >( self, visitor:V ) -> Result< V::Value, QueryError > {
+                let value = self.scalar( )?;
+                match value.trim( ).parse( ) {
+                    Ok( parsed ) => visitor.$visit( parsed ),
+                    Err( e ) => Err( QueryError::new( format!( "invalid value `{}`: {}", value, e ) ) ),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de, 'o> de::Deserializer<'de> for ValueDeserializer<'o> {
+    type Error = QueryError;
+
+    fn deserialize_any< V:Visitor<'de> >( self, visitor:V ) -> Result< V::Value, QueryError > {
+        match self.value {
+            Value::Raw( raw ) => visitor.visit_string( decode( &raw ) ),
+            Value::Bare => visitor.visit_string( String::new( ) ),
+            Value::List( _ ) => self.deserialize_seq( visitor ),
+            Value::Map( _ ) => self.deserialize_map( visitor ),
+        }
+    }
+
+    deserialize_parsed!( deserialize_i8 => visit_i8, deserialize_i16 => visit_i16, deserialize_i32 => visit_i32,
+                         deserialize_i64 => visit_i64, deserialize_u8 => visit_u8, deserialize_u16 => visit_u16,
+                         deserialize_u32 => visit_u32, deserialize_u64 => visit_u64, deserialize_f32 => visit_f32,
+                         deserialize_f64 => visit_f64, deserialize_char => visit_char );
+
+    fn deserialize_bool< V:Visitor<'de> >( self, visitor:V ) -> Result< V::Value, QueryError > {
+        let value = self.scalar( )?;
+        match value.trim( ) {
+            "true" | "1" => visitor.visit_bool( true ),
+            "false" | "0" => visitor.visit_bool( false ),
+            _ => Err( QueryError::new( format!( "invalid value `{}`: expected true or false", value ) ) ),
+        }
+    }
+
+    fn deserialize_str< V:Visitor<'de> >( self, visitor:V ) -> Result< V::Value, QueryError > {
+        visitor.visit_string( self.scalar( )? )
+    }
+
+    fn deserialize_string< V:Visitor<'de> >( self, visitor:V ) -> Result< V::Value, QueryError > {
+        visitor.visit_string( self.scalar( )? )
+    }
+
+    fn deserialize_identifier< V:Visitor<'de> >( self, visitor:V ) -> Result< V::Value, QueryError > {
+        visitor.visit_string( self.scalar( )? )
+    }
+
+    fn deserialize_bytes< V:Visitor<'de> >( self, _:V ) -> Result< V::Value, QueryError > {
+        Err( QueryError::new( "bytes cannot be read from a query" ) )
+    }
+
+    fn deserialize_byte_buf< V:Visitor<'de> >( self, visitor:V ) -> Result< V::Value, QueryError > {
+        self.deserialize_bytes( visitor )
+    }
+
+    fn deserialize_option< V:Visitor<'de> >( self, visitor:V ) -> Result< V::Value, QueryError > {
+        match &self.value {
+            Value::Raw( raw ) if raw.is_empty( ) => visitor.visit_none( ),
+            Value::Bare => visitor.visit_none( ),
+            _ => visitor.visit_some( self ),
+        }
+    }
+
+    fn deserialize_unit< V:Visitor<'de> >( self, visitor:V ) -> Result< V::Value, QueryError > {
+        visitor.visit_unit( )
+    }
+
+    fn deserialize_unit_struct< V:Visitor<'de> >( self, _:&'static str, visitor:V ) -> Result< V::Value, QueryError > {
+        visitor.visit_unit( )
+    }
+
+    fn deserialize_newtype_struct< V:Visitor<'de> >( self, _:&'static str, visitor:V ) -> Result< V::Value, QueryError > {
+        visitor.visit_newtype_struct( self )
+    }
+
+    fn deserialize_seq< V:Visitor<'de> >( self, visitor:V ) -> Result< V::Value, QueryError > {
+        let options = self.options;
+        let items = self.items( )?;
+        visitor.visit_seq( SeqAccessor{ items: items.into_iter( ).enumerate( ), options } )
+    }
+
+    fn deserialize_tuple< V:Visitor<'de> >( self, _:usize, visitor:V ) -> Result< V::Value, QueryError > {
+        self.deserialize_seq( visitor )
+    }
+
+    fn deserialize_tuple_struct< V:Visitor<'de> >( self, _:&'static str, _:usize, visitor:V ) -> Result< V::Value, QueryError > {
+        self.deserialize_seq( visitor )
+    }
+
+    fn deserialize_map< V:Visitor<'de> >( self, visitor:V ) -> Result< V::Value, QueryError > {
+        match self.value {
+            Value::Map( entries ) => visitor.visit_map( MapAccessor{ entries: entries.into_iter( ), value: None, options: self.options } ),
+            _ => Err( QueryError::new( "expected nested keys, found a value" ) ),
+        }
+    }
+
+    fn deserialize_struct< V:Visitor<'de> >( self, _:&'static str, _:&'static [&'static str], visitor:V ) -> Result< V::Value, QueryError > {
+        self.deserialize_map( visitor )
+    }
+
+    fn deserialize_enum< V:Visitor<'de> >( self, _:&'static str, _:&'static [&'static str], visitor:V ) -> Result< V::Value, QueryError > {
+        match self.value {
+            Value::Map( mut entries ) if entries.len( ) == 1 => {
+                let ( variant, value ) = entries.remove( 0 );
+                visitor.visit_enum( EnumAccessor{ variant, value: ValueDeserializer{ value, options: self.options } } )
+            }
+            _ => visitor.visit_enum( self.scalar( )?.into_deserializer( ) ),
+        }
+    }
+
+    fn deserialize_ignored_any< V:Visitor<'de> >( self, visitor:V ) -> Result< V::Value, QueryError > {
+        visitor.visit_unit( )
+    }
+}
+
+struct SeqAccessor<'o> {
+    items: std::iter::Enumerate< vec::IntoIter< Value > >,
+    options: &'o QueryOptions,
+}
+
+impl<'de, 'o> de::SeqAccess<'de> for SeqAccessor<'o> {
+    type Error = QueryError;
+
+    fn next_element_seed< T:DeserializeSeed<'de> >( &mut self, seed:T ) -> Result< Option< T::Value >, QueryError > {
+        match self.items.next( ) {
+            Some( ( index, value ) ) => seed.deserialize( ValueDeserializer{ value, options: self.options } )
+                                            .map( Some )
+                                            .map_err( |e| e.within( &index.to_string( ) ) ),
+            None => Ok( None ),
+        }
+    }
+}
+
+struct MapAccessor<'o> {
+    entries: vec::IntoIter< ( String, Value ) >,
+    value: Option< ( String, Value ) >,
+    options: &'o QueryOptions,
+}
+
+impl<'de, 'o> de::MapAccess<'de> for MapAccessor<'o> {
+    type Error = QueryError;
+
+    fn next_key_seed< K:DeserializeSeed<'de> >( &mut self, seed:K ) -> Result< Option< K::Value >, QueryError > {
+        match self.entries.next( ) {
+            Some( ( key, value ) ) => {
+                let result = seed.deserialize( key.clone( ).into_deserializer( ) ).map_err( |e:QueryError| e.within( &key ) );
+                self.value = Some( ( key, value ) );
+                result.map( Some )
+            }
+            None => Ok( None ),
+        }
+    }
+
+    fn next_value_seed< V:DeserializeSeed<'de> >( &mut self, seed:V ) -> Result< V::Value, QueryError > {
+        let ( key, value ) = self.value.take( ).ok_or_else( || QueryError::new( "a value was read before its key" ) )?;
+        seed.deserialize( ValueDeserializer{ value, options: self.options } ).map_err( |e| e.within( &key ) )
+    }
+}
+
+struct EnumAccessor<'o> {
+    variant: String,
+    value: ValueDeserializer<'o>,
+}
+
+impl<'de, 'o> de::EnumAccess<'de> for EnumAccessor<'o> {
+    type Error = QueryError;
+    type Variant = ValueDeserializer<'o>;
+
+    fn variant_seed< V:DeserializeSeed<'de> >( self, seed:V ) -> Result< ( V::Value, Self::Variant ), QueryError > {
+        let variant = seed.deserialize( self.variant.clone( ).into_deserializer( ) )?;
+        Ok( ( variant, self.value ) )
+    }
+}
+
+impl<'de, 'o> de::VariantAccess<'de> for ValueDeserializer<'o> {
+    type Error = QueryError;
+
+    fn unit_variant( self ) -> Result< (), QueryError > {
+        Err( QueryError::new( "expected a value, found nested keys" ) )
+    }
+
+    fn newtype_variant_seed< T:DeserializeSeed<'de> >( self, seed:T ) -> Result< T::Value, QueryError > {
+        seed.deserialize( self )
+    }
+
+    fn tuple_variant< V:Visitor<'de> >( self, _:usize, _:V ) -> Result< V::Value, QueryError > {
+        Err( QueryError::new( "tuple variants cannot be read from a query" ) )
+    }
+
+    fn struct_variant< V:Visitor<'de> >( self, _:&'static [&'static str], _:V ) -> Result< V::Value, QueryError > {
+        Err( QueryError::new( "struct variants cannot be read from a query" ) )
+    }
+}
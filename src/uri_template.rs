@@ -0,0 +1,659 @@
+/*!
+URI Templates as described by [RFC 6570](https://www.rfc-editor.org/rfc/rfc6570), supporting every
+operator and modifier up to and including level 4.
+
+A template is parsed once and may then be expanded any number of times. Expanding against a BaseUrl
+resolves the expansion the same way a relative reference is resolved, so a template beginning with
+`/` replaces the BaseUrl's path while one without extends the BaseUrl's directory.
+
+```
+use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+use base_url::uri_template::{ UriTemplate, Value };
+use std::collections::HashMap;
+
+# fn run( ) -> Result< (), BaseUrlError > {
+let api = BaseUrl::try_from( "https://api.example.org/" )?;
+let template = UriTemplate::parse( "/repos/{owner}/{repo}/issues{?state,labels*}" ).unwrap( );
+
+let mut vars = HashMap::new( );
+vars.insert( "owner".to_owned( ), Value::from( "rust-lang" ) );
+vars.insert( "repo".to_owned( ), Value::from( "rust" ) );
+vars.insert( "state".to_owned( ), Value::from( "open" ) );
+vars.insert( "labels".to_owned( ), Value::from( vec![ "A-docs", "E-easy" ] ) );
+
+let url = template.expand( &api, &vars ).unwrap( );
+assert_eq!( url.as_str( ), "https://api.example.org/repos/rust-lang/rust/issues?state=open&labels=A-docs&labels=E-easy" );
+# Ok( () )
+# }
+# run( );
+```
+ */
+
+use crate::{ BaseUrl, Url, ParseError };
+
+use std::collections::HashMap;
+
+/// The value bound to a template variable
+///
+/// Variables missing from the map of values, empty lists and empty maps are all treated as undefined
+/// and expand to nothing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    /// A single string
+    String( String ),
+    /// An ordered list of strings
+    List( Vec< String > ),
+    /// An ordered list of (key, value) pairs
+    Map( Vec< ( String, String ) > ),
+}
+
+impl<'a> From< &'a str > for Value {
+    fn from( value:&'a str ) -> Self {
+        Value::String( value.to_owned( ) )
+    }
+}
+
+impl From< String > for Value {
+    fn from( value:String ) -> Self {
+        Value::String( value )
+    }
+}
+
+impl<'a> From< Vec< &'a str > > for Value {
+    fn from( values:Vec< &'a str > ) -> Self {
+        Value::List( values.into_iter( ).map( |v| v.to_owned( ) ).collect( ) )
+    }
+}
+
+impl From< Vec< String > > for Value {
+    fn from( values:Vec< String > ) -> Self {
+        Value::List( values )
+    }
+}
+
+impl<'a> From< Vec< ( &'a str, &'a str ) > > for Value {
+    fn from( pairs:Vec< ( &'a str, &'a str ) > ) -> Self {
+        Value::Map( pairs.into_iter( ).map( |( k, v )| ( k.to_owned( ), v.to_owned( ) ) ).collect( ) )
+    }
+}
+
+impl From< Vec< ( String, String ) > > for Value {
+    fn from( pairs:Vec< ( String, String ) > ) -> Self {
+        Value::Map( pairs )
+    }
+}
+
+/// The ways in which a template can be malformed, or cannot be expanded with the values given. Each
+/// variant holds the byte offset of the problem
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TemplateError {
+    /// An expression was opened with `{` but never closed
+    UnclosedExpression( usize ),
+    /// A `}` appeared outside of an expression, or a `{` inside one
+    UnexpectedBrace( usize ),
+    /// An expression used one of the operators reserved for future extensions: `=`, `,`, `!`, `@`
+    /// or `|`
+    ReservedOperator( usize ),
+    /// A variable name was empty or contained characters other than letters, digits, `_`, `.` and
+    /// percent-encoded octets
+    InvalidVariable( usize ),
+    /// A prefix modifier was not a number from 1 to 9999
+    InvalidPrefix( usize ),
+    /// A variable with a prefix modifier was bound to a list or map, which section 2.4.1 of the RFC
+    /// does not allow. Holds the offset of the variable
+    CompositePrefix( usize ),
+}
+
+/// The ways in which expanding a template against a BaseUrl can fail
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExpandError {
+    /// The template could not be expanded with the values given
+    Template( TemplateError ),
+    /// The expansion could not be parsed as a Url reference
+    ParseError( ParseError ),
+}
+
+impl From< TemplateError > for ExpandError {
+    fn from( err:TemplateError ) -> Self {
+        ExpandError::Template( err )
+    }
+}
+
+impl From< ParseError > for ExpandError {
+    fn from( err:ParseError ) -> Self {
+        ExpandError::ParseError( err )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Simple,
+    Reserved,
+    Fragment,
+    Label,
+    PathSegment,
+    PathStyle,
+    Query,
+    QueryContinuation,
+}
+
+impl Operator {
+
+    fn from_char( c:char ) -> Option< Self > {
+        match c {
+            '+' => Some( Operator::Reserved ),
+            '#' => Some( Operator::Fragment ),
+            '.' => Some( Operator::Label ),
+            '/' => Some( Operator::PathSegment ),
+            ';' => Some( Operator::PathStyle ),
+            '?' => Some( Operator::Query ),
+            '&' => Some( Operator::QueryContinuation ),
+            _ => None,
+        }
+    }
+
+    /// The behaviour of each operator as tabulated in Appendix A of the RFC:
+    /// ( first, separator, named, if empty, allow reserved )
+    fn behaviour( self ) -> ( &'static str, &'static str, bool, &'static str, bool ) {
+        match self {
+            Operator::Simple => ( "", ",", false, "", false ),
+            Operator::Reserved => ( "", ",", false, "", true ),
+            Operator::Fragment => ( "#", ",", false, "", true ),
+            Operator::Label => ( ".", ".", false, "", false ),
+            Operator::PathSegment => ( "/", "/", false, "", false ),
+            Operator::PathStyle => ( ";", ";", true, "", false ),
+            Operator::Query => ( "?", "&", true, "=", false ),
+            Operator::QueryContinuation => ( "&", "&", true, "=", false ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Modifier {
+    None,
+    Prefix( usize ),
+    Explode,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct VarSpec {
+    name: String,
+    modifier: Modifier,
+    offset: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal( String ),
+    Expression( Operator, Vec< VarSpec > ),
+}
+
+/// A parsed URI Template
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UriTemplate {
+    template: String,
+    parts: Vec< Part >,
+}
+
+impl UriTemplate {
+
+    /// Parse a template
+    ///
+    /// # Errors
+    ///
+    /// A TemplateError describing the first problem found is returned if the template is malformed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::uri_template::{ UriTemplate, TemplateError };
+    ///
+    /// assert!( UriTemplate::parse( "/search{?q,lang}" ).is_ok( ) );
+    /// assert_eq!( UriTemplate::parse( "/users/{id" ), Err( TemplateError::UnclosedExpression( 7 ) ) );
+    /// assert_eq!( UriTemplate::parse( "/users/{id:0}" ), Err( TemplateError::InvalidPrefix( 11 ) ) );
+    /// ```
+    pub fn parse( template:&str ) -> Result< Self, TemplateError > {
+        let mut parts = Vec::new( );
+        let mut literal = String::new( );
+        let mut rest = template;
+        let mut offset = 0;
+
+        while let Some( i ) = rest.find( [ '{', '}' ] ) {
+            if rest[i..].starts_with( '}' ) {
+                return Err( TemplateError::UnexpectedBrace( offset + i ) );
+            }
+            literal.push_str( &rest[..i] );
+            let start = offset + i;
+            let body = &rest[i + 1..];
+            let end = match body.find( [ '{', '}' ] ) {
+                Some( j ) if body[j..].starts_with( '}' ) => j,
+                Some( j ) => return Err( TemplateError::UnexpectedBrace( start + 1 + j ) ),
+                None => return Err( TemplateError::UnclosedExpression( start ) ),
+            };
+
+            if !literal.is_empty( ) {
+                parts.push( Part::Literal( std::mem::take( &mut literal ) ) );
+            }
+            parts.push( parse_expression( &body[..end], start + 1 )? );
+
+            offset = start + 1 + end + 1;
+            rest = &template[offset..];
+        }
+        literal.push_str( rest );
+        if !literal.is_empty( ) {
+            parts.push( Part::Literal( literal ) );
+        }
+
+        Ok( UriTemplate{ template: template.to_owned( ), parts } )
+    }
+
+    /// Returns the template as it was given to `parse( )`
+    pub fn as_str( &self ) -> &str {
+        &self.template
+    }
+
+    /// Returns the names of every variable used by the template, in order of first appearance
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::uri_template::UriTemplate;
+    ///
+    /// let template = UriTemplate::parse( "/{owner}/{repo}{?page,owner}" ).unwrap( );
+    /// assert_eq!( template.variables( ), vec![ "owner", "repo", "page" ] );
+    /// ```
+    pub fn variables( &self ) -> Vec< &str > {
+        let mut names:Vec< &str > = Vec::new( );
+        for part in &self.parts {
+            if let Part::Expression( _, specs ) = part {
+                for spec in specs {
+                    if !names.contains( &spec.name.as_str( ) ) {
+                        names.push( &spec.name );
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    /// Expand the template into a string without resolving it against anything
+    ///
+    /// # Errors
+    ///
+    /// TemplateError::CompositePrefix is returned if a variable with a prefix modifier is bound to a
+    /// list or map.
+    ///
+    /// # Examples
+    ///
+    /// These are the examples given in section 3.2 of the RFC.
+    ///
+    /// ```rust
+    /// use base_url::uri_template::{ UriTemplate, TemplateError, Value };
+    /// use std::collections::HashMap;
+    ///
+    /// let mut vars = HashMap::new( );
+    /// vars.insert( "count".to_owned( ), Value::from( vec![ "one", "two", "three" ] ) );
+    /// vars.insert( "dom".to_owned( ), Value::from( vec![ "example", "com" ] ) );
+    /// vars.insert( "dub".to_owned( ), Value::from( "me/too" ) );
+    /// vars.insert( "hello".to_owned( ), Value::from( "Hello World!" ) );
+    /// vars.insert( "half".to_owned( ), Value::from( "50%" ) );
+    /// vars.insert( "var".to_owned( ), Value::from( "value" ) );
+    /// vars.insert( "who".to_owned( ), Value::from( "fred" ) );
+    /// vars.insert( "base".to_owned( ), Value::from( "http://example.com/home/" ) );
+    /// vars.insert( "path".to_owned( ), Value::from( "/foo/bar" ) );
+    /// vars.insert( "list".to_owned( ), Value::from( vec![ "red", "green", "blue" ] ) );
+    /// vars.insert( "keys".to_owned( ), Value::from( vec![ ( "semi", ";" ), ( "dot", "." ), ( "comma", "," ) ] ) );
+    /// vars.insert( "v".to_owned( ), Value::from( "6" ) );
+    /// vars.insert( "x".to_owned( ), Value::from( "1024" ) );
+    /// vars.insert( "y".to_owned( ), Value::from( "768" ) );
+    /// vars.insert( "empty".to_owned( ), Value::from( "" ) );
+    /// vars.insert( "empty_keys".to_owned( ), Value::Map( Vec::new( ) ) );
+    ///
+    /// let cases = [
+    ///     // 3.2.1 Variable Expansion
+    ///     ( "{count}", "one,two,three" ),
+    ///     ( "{count*}", "one,two,three" ),
+    ///     ( "{/count}", "/one,two,three" ),
+    ///     ( "{/count*}", "/one/two/three" ),
+    ///     ( "{;count}", ";count=one,two,three" ),
+    ///     ( "{;count*}", ";count=one;count=two;count=three" ),
+    ///     ( "{?count}", "?count=one,two,three" ),
+    ///     ( "{?count*}", "?count=one&count=two&count=three" ),
+    ///     ( "{&count*}", "&count=one&count=two&count=three" ),
+    ///     // 3.2.2 Simple String Expansion
+    ///     ( "{var}", "value" ),
+    ///     ( "{hello}", "Hello%20World%21" ),
+    ///     ( "{half}", "50%25" ),
+    ///     ( "O{empty}X", "OX" ),
+    ///     ( "O{undef}X", "OX" ),
+    ///     ( "{x,y}", "1024,768" ),
+    ///     ( "{x,hello,y}", "1024,Hello%20World%21,768" ),
+    ///     ( "?{x,empty}", "?1024," ),
+    ///     ( "?{x,undef}", "?1024" ),
+    ///     ( "?{undef,y}", "?768" ),
+    ///     ( "{var:3}", "val" ),
+    ///     ( "{var:30}", "value" ),
+    ///     ( "{list}", "red,green,blue" ),
+    ///     ( "{list*}", "red,green,blue" ),
+    ///     ( "{keys}", "semi,%3B,dot,.,comma,%2C" ),
+    ///     ( "{keys*}", "semi=%3B,dot=.,comma=%2C" ),
+    ///     // 3.2.3 Reserved Expansion
+    ///     ( "{+var}", "value" ),
+    ///     ( "{+hello}", "Hello%20World!" ),
+    ///     ( "{+half}", "50%25" ),
+    ///     ( "{base}index", "http%3A%2F%2Fexample.com%2Fhome%2Findex" ),
+    ///     ( "{+base}index", "http://example.com/home/index" ),
+    ///     ( "O{+empty}X", "OX" ),
+    ///     ( "O{+undef}X", "OX" ),
+    ///     ( "{+path}/here", "/foo/bar/here" ),
+    ///     ( "here?ref={+path}", "here?ref=/foo/bar" ),
+    ///     ( "up{+path}{var}/here", "up/foo/barvalue/here" ),
+    ///     ( "{+x,hello,y}", "1024,Hello%20World!,768" ),
+    ///     ( "{+path,x}/here", "/foo/bar,1024/here" ),
+    ///     ( "{+path:6}/here", "/foo/b/here" ),
+    ///     ( "{+list}", "red,green,blue" ),
+    ///     ( "{+list*}", "red,green,blue" ),
+    ///     ( "{+keys}", "semi,;,dot,.,comma,," ),
+    ///     ( "{+keys*}", "semi=;,dot=.,comma=," ),
+    ///     // 3.2.4 Fragment Expansion
+    ///     ( "{#var}", "#value" ),
+    ///     ( "{#hello}", "#Hello%20World!" ),
+    ///     ( "{#half}", "#50%25" ),
+    ///     ( "foo{#empty}", "foo#" ),
+    ///     ( "foo{#undef}", "foo" ),
+    ///     ( "{#x,hello,y}", "#1024,Hello%20World!,768" ),
+    ///     ( "{#path,x}/here", "#/foo/bar,1024/here" ),
+    ///     ( "{#path:6}/here", "#/foo/b/here" ),
+    ///     ( "{#list}", "#red,green,blue" ),
+    ///     ( "{#list*}", "#red,green,blue" ),
+    ///     ( "{#keys}", "#semi,;,dot,.,comma,," ),
+    ///     ( "{#keys*}", "#semi=;,dot=.,comma=," ),
+    ///     // 3.2.5 Label Expansion with Dot-Prefix
+    ///     ( "{.who}", ".fred" ),
+    ///     ( "{.who,who}", ".fred.fred" ),
+    ///     ( "{.half,who}", ".50%25.fred" ),
+    ///     ( "www{.dom*}", "www.example.com" ),
+    ///     ( "X{.var}", "X.value" ),
+    ///     ( "X{.empty}", "X." ),
+    ///     ( "X{.undef}", "X" ),
+    ///     ( "X{.var:3}", "X.val" ),
+    ///     ( "X{.list}", "X.red,green,blue" ),
+    ///     ( "X{.list*}", "X.red.green.blue" ),
+    ///     ( "X{.keys}", "X.semi,%3B,dot,.,comma,%2C" ),
+    ///     ( "X{.keys*}", "X.semi=%3B.dot=..comma=%2C" ),
+    ///     ( "X{.empty_keys}", "X" ),
+    ///     ( "X{.empty_keys*}", "X" ),
+    ///     // 3.2.6 Path Segment Expansion
+    ///     ( "{/who}", "/fred" ),
+    ///     ( "{/who,who}", "/fred/fred" ),
+    ///     ( "{/half,who}", "/50%25/fred" ),
+    ///     ( "{/who,dub}", "/fred/me%2Ftoo" ),
+    ///     ( "{/var}", "/value" ),
+    ///     ( "{/var,empty}", "/value/" ),
+    ///     ( "{/var,undef}", "/value" ),
+    ///     ( "{/var,x}/here", "/value/1024/here" ),
+    ///     ( "{/var:1,var}", "/v/value" ),
+    ///     ( "{/list}", "/red,green,blue" ),
+    ///     ( "{/list*}", "/red/green/blue" ),
+    ///     ( "{/list*,path:4}", "/red/green/blue/%2Ffoo" ),
+    ///     ( "{/keys}", "/semi,%3B,dot,.,comma,%2C" ),
+    ///     ( "{/keys*}", "/semi=%3B/dot=./comma=%2C" ),
+    ///     // 3.2.7 Path-Style Parameter Expansion
+    ///     ( "{;who}", ";who=fred" ),
+    ///     ( "{;half}", ";half=50%25" ),
+    ///     ( "{;empty}", ";empty" ),
+    ///     ( "{;v,empty,who}", ";v=6;empty;who=fred" ),
+    ///     ( "{;v,bar,who}", ";v=6;who=fred" ),
+    ///     ( "{;x,y}", ";x=1024;y=768" ),
+    ///     ( "{;x,y,empty}", ";x=1024;y=768;empty" ),
+    ///     ( "{;x,y,undef}", ";x=1024;y=768" ),
+    ///     ( "{;hello:5}", ";hello=Hello" ),
+    ///     ( "{;list}", ";list=red,green,blue" ),
+    ///     ( "{;list*}", ";list=red;list=green;list=blue" ),
+    ///     ( "{;keys}", ";keys=semi,%3B,dot,.,comma,%2C" ),
+    ///     ( "{;keys*}", ";semi=%3B;dot=.;comma=%2C" ),
+    ///     // 3.2.8 Form-Style Query Expansion
+    ///     ( "{?who}", "?who=fred" ),
+    ///     ( "{?half}", "?half=50%25" ),
+    ///     ( "{?x,y}", "?x=1024&y=768" ),
+    ///     ( "{?x,y,empty}", "?x=1024&y=768&empty=" ),
+    ///     ( "{?x,y,undef}", "?x=1024&y=768" ),
+    ///     ( "{?var:3}", "?var=val" ),
+    ///     ( "{?list}", "?list=red,green,blue" ),
+    ///     ( "{?list*}", "?list=red&list=green&list=blue" ),
+    ///     ( "{?keys}", "?keys=semi,%3B,dot,.,comma,%2C" ),
+    ///     ( "{?keys*}", "?semi=%3B&dot=.&comma=%2C" ),
+    ///     // 3.2.9 Form-Style Query Continuation
+    ///     ( "{&who}", "&who=fred" ),
+    ///     ( "{&half}", "&half=50%25" ),
+    ///     ( "?fixed=yes{&x}", "?fixed=yes&x=1024" ),
+    ///     ( "{&x,y,empty}", "&x=1024&y=768&empty=" ),
+    ///     ( "{&var:3}", "&var=val" ),
+    ///     ( "{&list}", "&list=red,green,blue" ),
+    ///     ( "{&list*}", "&list=red&list=green&list=blue" ),
+    ///     ( "{&keys}", "&keys=semi,%3B,dot,.,comma,%2C" ),
+    ///     ( "{&keys*}", "&semi=%3B&dot=.&comma=%2C" ),
+    /// ];
+    ///
+    /// for &( template, expected ) in cases.iter( ) {
+    ///     assert_eq!( UriTemplate::parse( template ).unwrap( ).expand_str( &vars ).unwrap( ), expected, "{}", template );
+    /// }
+    ///
+    /// // Prefixes only apply to strings
+    /// let template = UriTemplate::parse( "/x{?list:1}" ).unwrap( );
+    /// assert_eq!( template.expand_str( &vars ), Err( TemplateError::CompositePrefix( 4 ) ) );
+    /// ```
+    pub fn expand_str( &self, vars:&HashMap< String, Value > ) -> Result< String, TemplateError > {
+        let mut out = String::new( );
+        for part in &self.parts {
+            match part {
+                Part::Literal( literal ) => encode_into( &mut out, literal, true ),
+                Part::Expression( op, specs ) => expand_expression( &mut out, *op, specs, vars )?,
+            }
+        }
+        Ok( out )
+    }
+
+    /// Expand the template and resolve the result against the given BaseUrl
+    ///
+    /// # Errors
+    ///
+    /// An ExpandError is returned if the template cannot be expanded with the values given, as
+    /// with `expand_str( )`, or if the expansion cannot be parsed as a Url reference.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::uri_template::{ UriTemplate, Value };
+    /// use std::collections::HashMap;
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let base = BaseUrl::try_from( "https://example.org/api/v2/" )?;
+    /// let mut vars = HashMap::new( );
+    /// vars.insert( "id".to_owned( ), Value::from( "42" ) );
+    ///
+    /// let relative = UriTemplate::parse( "users/{id}" ).unwrap( );
+    /// assert_eq!( relative.expand( &base, &vars ).unwrap( ).as_str( ), "https://example.org/api/v2/users/42" );
+    ///
+    /// let absolute = UriTemplate::parse( "/users/{id}" ).unwrap( );
+    /// assert_eq!( absolute.expand( &base, &vars ).unwrap( ).as_str( ), "https://example.org/users/42" );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn expand( &self, base:&BaseUrl, vars:&HashMap< String, Value > ) -> Result< Url, ExpandError > {
+        Ok( base.url.join( &self.expand_str( vars )? )? )
+    }
+}
+
+fn parse_expression( body:&str, offset:usize ) -> Result< Part, TemplateError > {
+    let mut chars = body.chars( );
+    let ( op, list, list_offset ) = match chars.next( ) {
+        Some( c ) if "=,!@|".contains( c ) => return Err( TemplateError::ReservedOperator( offset ) ),
+        Some( c ) => match Operator::from_char( c ) {
+            Some( op ) => ( op, &body[1..], offset + 1 ),
+            None => ( Operator::Simple, body, offset ),
+        },
+        None => return Err( TemplateError::InvalidVariable( offset ) ),
+    };
+
+    let mut specs = Vec::new( );
+    let mut spec_offset = list_offset;
+    for spec in list.split( ',' ) {
+        specs.push( parse_varspec( spec, spec_offset )? );
+        spec_offset += spec.len( ) + 1;
+    }
+    Ok( Part::Expression( op, specs ) )
+}
+
+fn parse_varspec( spec:&str, offset:usize ) -> Result< VarSpec, TemplateError > {
+    let ( name, modifier ) = if let Some( name ) = spec.strip_suffix( '*' ) {
+        ( name, Modifier::Explode )
+    } else if let Some( i ) = spec.find( ':' ) {
+        let digits = &spec[i + 1..];
+        let length = match digits.parse::< usize >( ) {
+            Ok( n ) if ( 1..10000 ).contains( &n ) && !digits.starts_with( '+' ) => n,
+            _ => return Err( TemplateError::InvalidPrefix( offset + i + 1 ) ),
+        };
+        ( &spec[..i], Modifier::Prefix( length ) )
+    } else {
+        ( spec, Modifier::None )
+    };
+
+    if !valid_varname( name ) {
+        return Err( TemplateError::InvalidVariable( offset ) );
+    }
+    Ok( VarSpec{ name: name.to_owned( ), modifier, offset } )
+}
+
+/// varname = varchar *( ["."] varchar ), varchar = ALPHA / DIGIT / "_" / pct-encoded
+fn valid_varname( name:&str ) -> bool {
+    let bytes = name.as_bytes( );
+    if bytes.is_empty( ) || bytes[0] == b'.' || bytes[bytes.len( ) - 1] == b'.' {
+        return false;
+    }
+    let mut i = 0;
+    while i < bytes.len( ) {
+        match bytes[i] {
+            b'%' => {
+                if i + 2 >= bytes.len( ) || !bytes[i + 1].is_ascii_hexdigit( ) || !bytes[i + 2].is_ascii_hexdigit( ) {
+                    return false;
+                }
+                i += 2;
+            }
+            b'.' if bytes[i - 1] == b'.' => return false,
+            b if b.is_ascii_alphanumeric( ) || b == b'_' || b == b'.' => {}
+            _ => return false,
+        }
+        i += 1;
+    }
+    true
+}
+
+fn expand_expression( out:&mut String, op:Operator, specs:&[VarSpec], vars:&HashMap< String, Value > ) -> Result< (), TemplateError > {
+    let ( first, sep, named, if_empty, reserved ) = op.behaviour( );
+    let mut first_defined = true;
+
+    for spec in specs {
+        let value = match vars.get( &spec.name ) {
+            Some( Value::List( l ) ) if l.is_empty( ) => continue,
+            Some( Value::Map( m ) ) if m.is_empty( ) => continue,
+            Some( value ) => value,
+            None => continue,
+        };
+        if let ( Value::List( _ ), Modifier::Prefix( _ ) ) | ( Value::Map( _ ), Modifier::Prefix( _ ) ) = ( value, spec.modifier ) {
+            return Err( TemplateError::CompositePrefix( spec.offset ) );
+        }
+
+        out.push_str( if first_defined { first } else { sep } );
+        first_defined = false;
+
+        match ( value, spec.modifier ) {
+            ( Value::String( s ), modifier ) => {
+                if named {
+                    out.push_str( &spec.name );
+                    out.push_str( if s.is_empty( ) { if_empty } else { "=" } );
+                }
+                let s = match modifier {
+                    Modifier::Prefix( n ) => match s.char_indices( ).nth( n ) {
+                        Some( ( i, _ ) ) => &s[..i],
+                        None => &s[..],
+                    },
+                    _ => &s[..],
+                };
+                encode_into( out, s, reserved );
+            }
+            ( Value::List( items ), Modifier::Explode ) => {
+                for ( i, item ) in items.iter( ).enumerate( ) {
+                    if i > 0 {
+                        out.push_str( sep );
+                    }
+                    if named {
+                        out.push_str( &spec.name );
+                        out.push_str( if item.is_empty( ) { if_empty } else { "=" } );
+                    }
+                    encode_into( out, item, reserved );
+                }
+            }
+            ( Value::Map( pairs ), Modifier::Explode ) => {
+                for ( i, ( key, val ) ) in pairs.iter( ).enumerate( ) {
+                    if i > 0 {
+                        out.push_str( sep );
+                    }
+                    encode_into( out, key, reserved );
+                    out.push_str( if named && val.is_empty( ) { if_empty } else { "=" } );
+                    encode_into( out, val, reserved );
+                }
+            }
+            ( Value::List( items ), _ ) => {
+                if named {
+                    out.push_str( &spec.name );
+                    out.push( '=' );
+                }
+                for ( i, item ) in items.iter( ).enumerate( ) {
+                    if i > 0 {
+                        out.push( ',' );
+                    }
+                    encode_into( out, item, reserved );
+                }
+            }
+            ( Value::Map( pairs ), _ ) => {
+                if named {
+                    out.push_str( &spec.name );
+                    out.push( '=' );
+                }
+                for ( i, ( key, val ) ) in pairs.iter( ).enumerate( ) {
+                    if i > 0 {
+                        out.push( ',' );
+                    }
+                    encode_into( out, key, reserved );
+                    out.push( ',' );
+                    encode_into( out, val, reserved );
+                }
+            }
+        }
+    }
+    Ok( () )
+}
+
+/// Percent-encode everything but unreserved characters, and also reserved characters and existing
+/// percent-encoded triplets if `reserved` is set
+fn encode_into( out:&mut String, input:&str, reserved:bool ) {
+    let bytes = input.as_bytes( );
+    let mut i = 0;
+    while i < bytes.len( ) {
+        let b = bytes[i];
+        let keep = b.is_ascii_alphanumeric( )
+            || b"-._~".contains( &b )
+            || ( reserved && b":/?#[]@!$&'()*+,;=".contains( &b ) )
+            || ( reserved && b == b'%' && i + 2 < bytes.len( )
+                 && bytes[i + 1].is_ascii_hexdigit( ) && bytes[i + 2].is_ascii_hexdigit( ) );
+        if keep {
+            out.push( b as char );
+        } else {
+            out.push_str( &format!( "%{:02X}", b ) );
+        }
+        i += 1;
+    }
+}
@@ -0,0 +1,296 @@
+/*!
+A map keyed by BaseUrl which can find the most specific entry for any Url.
+
+Keys are stored in a trie, first by origin and then by path segment, so lookups only consider whole
+segments: `https://api.example.org/v1/` covers `https://api.example.org/v1/users` but not
+`https://api.example.org/v10/users`. A trailing slash on a key is not significant, nor are its
+credentials, query or fragment.
+
+```
+use base_url::{ BaseUrl, BaseUrlError, Url, TryFrom };
+use base_url::url_map::BaseUrlMap;
+
+# fn run( ) -> Result< (), BaseUrlError > {
+let mut routes = BaseUrlMap::new( );
+routes.insert( &BaseUrl::try_from( "https://api.example.org/v1/" )?, "api" );
+routes.insert( &BaseUrl::try_from( "https://api.example.org/v1/admin/" )?, "admin" );
+
+let ( base, handler ) = routes.longest_match( &Url::parse( "https://api.example.org/v1/admin/users?page=2" )? ).unwrap( );
+assert_eq!( base.as_str( ), "https://api.example.org/v1/admin/" );
+assert_eq!( *handler, "admin" );
+
+assert_eq!( routes.longest_match( &Url::parse( "https://api.example.org/v1/users" )? ).unwrap( ).1, &"api" );
+assert!( routes.longest_match( &Url::parse( "https://api.example.org/v10/users" )? ).is_none( ) );
+assert!( routes.longest_match( &Url::parse( "http://api.example.org/v1/users" )? ).is_none( ) );
+# Ok( () )
+# }
+# run( );
+```
+ */
+
+use crate::{ BaseUrl, Host, OriginTuple, Url };
+
+use std::collections::btree_map::{ self, BTreeMap };
+
+#[derive(Clone, Debug)]
+struct Node<V> {
+    entry: Option< ( BaseUrl, V ) >,
+    children: BTreeMap< String, Node<V> >,
+}
+
+impl<V> Node<V> {
+
+    fn new( ) -> Self {
+        Node{ entry: None, children: BTreeMap::new( ) }
+    }
+
+    fn is_empty( &self ) -> bool {
+        self.entry.is_none( ) && self.children.is_empty( )
+    }
+
+    /// Remove the entry at the end of the path, pruning any nodes left empty
+    fn remove( &mut self, segments:&[&str] ) -> Option< V > {
+        match segments.split_first( ) {
+            None => self.entry.take( ).map( |( _, value )| value ),
+            Some( ( first, rest ) ) => {
+                let child = self.children.get_mut( *first )?;
+                let removed = child.remove( rest );
+                if child.is_empty( ) {
+                    self.children.remove( *first );
+                }
+                removed
+            }
+        }
+    }
+}
+
+/// A map from BaseUrls to values supporting longest-prefix lookups
+#[derive(Clone, Debug)]
+pub struct BaseUrlMap<V> {
+    origins: BTreeMap< OriginTuple, Node<V> >,
+    len: usize,
+}
+
+impl<V> Default for BaseUrlMap<V> {
+    fn default( ) -> Self {
+        BaseUrlMap::new( )
+    }
+}
+
+impl<V> BaseUrlMap<V> {
+
+    /// Create an empty map
+    pub fn new( ) -> Self {
+        BaseUrlMap{ origins: BTreeMap::new( ), len: 0 }
+    }
+
+    /// Returns the number of entries in the map
+    pub fn len( &self ) -> usize {
+        self.len
+    }
+
+    /// Returns true if the map has no entries
+    pub fn is_empty( &self ) -> bool {
+        self.len == 0
+    }
+
+    /// Insert a value under the given BaseUrl, returning the value it replaces if there was one.
+    ///
+    /// The stored key is replaced as well, so that a later match reports the most recently inserted
+    /// spelling of it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::url_map::BaseUrlMap;
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let mut map = BaseUrlMap::new( );
+    /// assert_eq!( map.insert( &BaseUrl::try_from( "https://example.org/docs" )?, 1 ), None );
+    /// assert_eq!( map.insert( &BaseUrl::try_from( "https://example.org/docs/" )?, 2 ), Some( 1 ) );
+    /// assert_eq!( map.len( ), 1 );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn insert( &mut self, key:&BaseUrl, value:V ) -> Option< V > {
+        let mut node = self.origins.entry( origin_key( key.as_ref( ) ) ).or_insert_with( Node::new );
+        for segment in key_segments( key ) {
+            node = node.children.entry( segment.to_owned( ) ).or_insert_with( Node::new );
+        }
+        let previous = node.entry.replace( ( key.clone( ), value ) ).map( |( _, value )| value );
+        if previous.is_none( ) {
+            self.len += 1;
+        }
+        previous
+    }
+
+    /// Remove the value stored under exactly the given BaseUrl, returning it
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, Url, TryFrom };
+    /// use base_url::url_map::BaseUrlMap;
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let mut map = BaseUrlMap::new( );
+    /// let root = BaseUrl::try_from( "https://example.org/" )?;
+    /// let docs = BaseUrl::try_from( "https://example.org/docs/" )?;
+    /// map.insert( &root, "site" );
+    /// map.insert( &docs, "docs" );
+    ///
+    /// assert_eq!( map.remove( &docs ), Some( "docs" ) );
+    /// assert_eq!( map.remove( &docs ), None );
+    /// assert_eq!( map.longest_match( &Url::parse( "https://example.org/docs/intro" )? ).unwrap( ).1, &"site" );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn remove( &mut self, key:&BaseUrl ) -> Option< V > {
+        let origin = origin_key( key.as_ref( ) );
+        let segments:Vec< &str > = key_segments( key ).collect( );
+        let root = self.origins.get_mut( &origin )?;
+        let removed = root.remove( &segments );
+        if root.is_empty( ) {
+            self.origins.remove( &origin );
+        }
+        if removed.is_some( ) {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Returns the value stored under exactly the given BaseUrl
+    pub fn get( &self, key:&BaseUrl ) -> Option< &V > {
+        self.find( key ).and_then( |node| node.entry.as_ref( ) ).map( |( _, value )| value )
+    }
+
+    /// Returns a mutable reference to the value stored under exactly the given BaseUrl
+    pub fn get_mut( &mut self, key:&BaseUrl ) -> Option< &mut V > {
+        let mut node = self.origins.get_mut( &origin_key( key.as_ref( ) ) )?;
+        for segment in key_segments( key ) {
+            node = node.children.get_mut( segment )?;
+        }
+        node.entry.as_mut( ).map( |( _, value )| value )
+    }
+
+    /// Returns true if a value is stored under exactly the given BaseUrl
+    pub fn contains_key( &self, key:&BaseUrl ) -> bool {
+        self.get( key ).is_some( )
+    }
+
+    /// Find the entry whose key is the longest prefix of the given Url, by whole path segments,
+    /// within the same origin. Returns the key along with its value.
+    ///
+    /// Only the Url's scheme, host, port and path are considered. Urls without a host, such as
+    /// `data:` Urls, never match.
+    pub fn longest_match( &self, url:&Url ) -> Option< ( &BaseUrl, &V ) > {
+        url.host( )?;
+        let mut node = self.origins.get( &origin_key( url ) )?;
+        let mut best = node.entry.as_ref( );
+        for segment in url.path_segments( ).into_iter( ).flatten( ) {
+            node = match node.children.get( segment ) {
+                Some( child ) => child,
+                None => break,
+            };
+            best = node.entry.as_ref( ).or( best );
+        }
+        best.map( |( key, value )| ( key, value ) )
+    }
+
+    /// Iterate over every entry in the map. Entries are ordered by origin and then by path, with
+    /// each key coming before the keys it is a prefix of.
+    pub fn iter( &self ) -> Iter<'_, V> {
+        Iter{ origins: Some( self.origins.values( ) ), stack: Vec::new( ) }
+    }
+
+    /// Iterate over the entries whose keys lie under the given BaseUrl, including the entry for the
+    /// BaseUrl itself if there is one
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, TryFrom };
+    /// use base_url::url_map::BaseUrlMap;
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let mut map = BaseUrlMap::new( );
+    /// for ( i, key ) in [ "https://x.org/v1/", "https://x.org/v1/admin/", "https://x.org/v10/", "https://x.org/" ].iter( ).enumerate( ) {
+    ///     map.insert( &BaseUrl::try_from( *key )?, i );
+    /// }
+    ///
+    /// let under:Vec< &str > = map.iter_prefix( &BaseUrl::try_from( "https://x.org/v1" )? )
+    ///                            .map( |( key, _ )| key.as_str( ) )
+    ///                            .collect( );
+    /// assert_eq!( under, vec![ "https://x.org/v1/", "https://x.org/v1/admin/" ] );
+    /// assert_eq!( map.iter( ).count( ), 4 );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn iter_prefix( &self, prefix:&BaseUrl ) -> Iter<'_, V> {
+        Iter{ origins: None, stack: self.find( prefix ).into_iter( ).collect( ) }
+    }
+
+    fn find( &self, key:&BaseUrl ) -> Option< &Node<V> > {
+        let mut node = self.origins.get( &origin_key( key.as_ref( ) ) )?;
+        for segment in key_segments( key ) {
+            node = node.children.get( segment )?;
+        }
+        Some( node )
+    }
+}
+
+/// An iterator over the entries of a BaseUrlMap
+pub struct Iter<'a, V> {
+    origins: Option< btree_map::Values<'a, OriginTuple, Node<V>> >,
+    stack: Vec< &'a Node<V> >,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = ( &'a BaseUrl, &'a V );
+
+    fn next( &mut self ) -> Option< Self::Item > {
+        loop {
+            let node = match self.stack.pop( ) {
+                Some( node ) => node,
+                None => self.next_origin( )?,
+            };
+            self.stack.extend( node.children.values( ).rev( ) );
+            if let Some( ( key, value ) ) = &node.entry {
+                return Some( ( key, value ) );
+            }
+        }
+    }
+}
+
+impl<'a, V> Iter<'a, V> {
+    fn next_origin( &mut self ) -> Option< &'a Node<V> > {
+        self.origins.as_mut( )?.next( )
+    }
+}
+
+impl<'a, V> IntoIterator for &'a BaseUrlMap<V> {
+    type Item = ( &'a BaseUrl, &'a V );
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter( self ) -> Self::IntoIter {
+        self.iter( )
+    }
+}
+
+/// The scheme, host and port of a Url. Unlike Url::origin this is not opaque for `file:` Urls with a
+/// host
+fn origin_key( url:&Url ) -> OriginTuple {
+    let host = url.host( ).map( |h| h.to_owned( ) ).unwrap_or( Host::Domain( String::new( ) ) );
+    ( url.scheme( ).to_owned( ), host, url.port_or_known_default( ).unwrap_or( 0 ) )
+}
+
+/// The path segments of a key, without the empty segment a trailing slash leaves
+fn key_segments( key:&BaseUrl ) -> impl Iterator< Item = &str > {
+    let path = key.path( );
+    let path = path.strip_suffix( '/' ).unwrap_or( path );
+    path.split( '/' ).skip( 1 )
+}
@@ -0,0 +1,1578 @@
+/*!
+An implementation of the [URL Pattern Standard](https://urlpattern.spec.whatwg.org/), matching Urls
+with the same semantics as the `URLPattern` API found in browsers.
+
+A pattern holds one compiled pattern per component of a Url: protocol, username, password, hostname,
+port, pathname, search and hash. Each may contain named groups such as `:id`, regular expression
+groups such as `(\\d+)`, wildcards and `{ }` groups with the `?`, `*` and `+` modifiers. Patterns are
+compiled once and can then be matched against any number of Urls or BaseUrls.
+
+Patterns can be written as a single string, which must either contain a protocol or be resolved
+against a BaseUrl, or component by component with a UrlPatternInit.
+
+```
+use base_url::{ BaseUrl, BaseUrlError, Url, TryFrom };
+use base_url::urlpattern::{ UrlPattern, UrlPatternOptions };
+
+# fn run( ) -> Result< (), BaseUrlError > {
+let pattern = UrlPattern::parse( "https://:tenant.example.org/books/:id(\\d+)", None,
+                                 UrlPatternOptions::default( ) ).unwrap( );
+
+let url = Url::parse( "https://acme.example.org/books/123" )?;
+let result = pattern.exec( &url ).unwrap( );
+assert_eq!( result.hostname.group( "tenant" ), Some( "acme" ) );
+assert_eq!( result.pathname.group( "id" ), Some( "123" ) );
+
+let base = BaseUrl::try_from( "https://acme.example.org/books/abc" )?;
+assert!( !pattern.test( base.as_ref( ) ) );
+# Ok( () )
+# }
+# run( );
+```
+
+Regular expression groups are compiled with the [regex](https://docs.rs/regex) crate rather than an
+ECMAScript engine, so features it lacks such as look-around and back-references are rejected with
+UrlPatternError::Regex.
+ */
+
+use crate::{ BaseUrl, Url };
+
+use regex::Regex;
+
+use unicode_xid::UnicodeXID;
+
+use url::percent_encoding::{ utf8_percent_encode, SIMPLE_ENCODE_SET };
+
+/// The components of a Url which a pattern matches separately
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Component {
+    Protocol,
+    Username,
+    Password,
+    Hostname,
+    Port,
+    Pathname,
+    Search,
+    Hash,
+}
+
+/// The ways in which building a pattern can fail
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UrlPatternError {
+    /// A component's pattern could not be tokenized or parsed. Holds the component and the index, in
+    /// characters, of the problem
+    Syntax( Component, usize ),
+    /// The same group name was used twice within a component
+    DuplicateName( Component, String ),
+    /// A component's generated regular expression could not be compiled. Holds the compiler's message
+    Regex( Component, String ),
+    /// Fixed text in a component is not valid for that component, for example a hostname containing
+    /// a space
+    InvalidText( Component, String ),
+    /// A pattern string without a protocol was given without a BaseUrl to resolve it against
+    MissingBase,
+}
+
+/// Options affecting how a pattern is compiled
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct UrlPatternOptions {
+    /// Match the pathname, search and hash components without regard to case
+    pub ignore_case: bool,
+}
+
+/// A pattern, or a Url to match, given component by component
+///
+/// When building a pattern, components which are not given match anything unless they are inherited
+/// from the `base_url`. When matching, components which are not given are empty.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UrlPatternInit {
+    pub protocol: Option< String >,
+    pub username: Option< String >,
+    pub password: Option< String >,
+    pub hostname: Option< String >,
+    pub port: Option< String >,
+    pub pathname: Option< String >,
+    pub search: Option< String >,
+    pub hash: Option< String >,
+    /// The BaseUrl to resolve a relative pathname against and to inherit missing components from
+    pub base_url: Option< BaseUrl >,
+}
+
+/// The result of matching a single component
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ComponentResult {
+    /// The component of the Url which was matched
+    pub input: String,
+    /// Each group of the component's pattern in order, along with what it matched. Optional groups
+    /// which did not participate in the match hold None
+    pub groups: Vec< ( String, Option< String > ) >,
+}
+
+impl ComponentResult {
+
+    /// Returns the text matched by the named group. Unnamed groups are named by their index, starting
+    /// from "0".
+    pub fn group( &self, name:&str ) -> Option< &str > {
+        self.groups.iter( )
+                   .find( |( n, _ )| n == name )
+                   .and_then( |( _, value )| value.as_ref( ).map( |v| v.as_str( ) ) )
+    }
+}
+
+/// The result of a successful match
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UrlPatternResult {
+    pub protocol: ComponentResult,
+    pub username: ComponentResult,
+    pub password: ComponentResult,
+    pub hostname: ComponentResult,
+    pub port: ComponentResult,
+    pub pathname: ComponentResult,
+    pub search: ComponentResult,
+    pub hash: ComponentResult,
+}
+
+/// A compiled URL pattern
+#[derive(Clone, Debug)]
+pub struct UrlPattern {
+    protocol: CompiledComponent,
+    username: CompiledComponent,
+    password: CompiledComponent,
+    hostname: CompiledComponent,
+    port: CompiledComponent,
+    pathname: CompiledComponent,
+    search: CompiledComponent,
+    hash: CompiledComponent,
+}
+
+impl UrlPattern {
+
+    /// Compile a pattern written as a single string such as `https://*.example.org/:path*`.
+    ///
+    /// Components left out of the string which come before those present, such as the protocol and
+    /// hostname of `/books/*`, are inherited from the BaseUrl. Any others match anything.
+    ///
+    /// # Errors
+    ///
+    /// If the pattern is malformed the first problem found is returned. If the string has no protocol
+    /// and no BaseUrl is given UrlPatternError::MissingBase is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ BaseUrl, BaseUrlError, Url, TryFrom };
+    /// use base_url::urlpattern::{ UrlPattern, UrlPatternOptions, UrlPatternError };
+    ///
+    ///# fn run( ) -> Result< (), BaseUrlError > {
+    /// let base = BaseUrl::try_from( "https://example.org/docs/" )?;
+    /// let pattern = UrlPattern::parse( "guide/*", Some( &base ), UrlPatternOptions::default( ) ).unwrap( );
+    ///
+    /// assert_eq!( pattern.hostname( ), "example.org" );
+    /// assert_eq!( pattern.pathname( ), "/docs/guide/*" );
+    /// assert!( pattern.test( &Url::parse( "https://example.org/docs/guide/intro" )? ) );
+    /// assert!( pattern.test( &Url::parse( "https://example.org/docs/guide/intro?lang=fr" )? ) );
+    /// assert!( !pattern.test( &Url::parse( "https://example.com/docs/guide/intro" )? ) );
+    ///
+    /// let options = UrlPatternOptions{ ignore_case: true };
+    /// assert_eq!( UrlPattern::parse( "/guide", None, options ).unwrap_err( ), UrlPatternError::MissingBase );
+    ///# Ok( () )
+    ///# }
+    ///# run( );
+    /// ```
+    pub fn parse( pattern:&str, base:Option< &BaseUrl >, options:UrlPatternOptions ) -> Result< Self, UrlPatternError > {
+        let mut init = parse_constructor_string( pattern )?;
+        if base.is_none( ) && init.protocol.is_none( ) {
+            return Err( UrlPatternError::MissingBase );
+        }
+        init.base_url = base.cloned( );
+        UrlPattern::from_init( &init, options )
+    }
+
+    /// Compile a pattern given component by component
+    ///
+    /// # Errors
+    ///
+    /// If any component's pattern is malformed the first problem found is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::{ Url, ParseError };
+    /// use base_url::urlpattern::{ UrlPattern, UrlPatternInit, UrlPatternOptions };
+    ///
+    ///# fn run( ) -> Result< (), ParseError > {
+    /// let init = UrlPatternInit{
+    ///     pathname: Some( "/:category/:item+".to_owned( ) ),
+    ///     search: Some( "*".to_owned( ) ),
+    ///     ..UrlPatternInit::default( )
+    /// };
+    /// let pattern = UrlPattern::from_init( &init, UrlPatternOptions::default( ) ).unwrap( );
+    ///
+    /// let result = pattern.exec( &Url::parse( "http://shop.example.org/tools/saws/hand?sort=price" )? ).unwrap( );
+    /// assert_eq!( result.pathname.group( "category" ), Some( "tools" ) );
+    /// assert_eq!( result.pathname.group( "item" ), Some( "saws/hand" ) );
+    /// assert_eq!( result.search.group( "0" ), Some( "sort=price" ) );
+    ///# Ok( () )
+    ///# }
+    ///# run( ).unwrap( );
+    /// ```
+    pub fn from_init( init:&UrlPatternInit, options:UrlPatternOptions ) -> Result< Self, UrlPatternError > {
+        let processed = process_init( init, InitType::Pattern, None )?;
+        let wildcard = |value:Option< String >| value.unwrap_or_else( || "*".to_owned( ) );
+        let protocol = wildcard( processed.protocol );
+        let username = wildcard( processed.username );
+        let password = wildcard( processed.password );
+        let hostname = wildcard( processed.hostname );
+        let mut port = wildcard( processed.port );
+        let pathname = wildcard( processed.pathname );
+        let search = wildcard( processed.search );
+        let hash = wildcard( processed.hash );
+
+        if let Some( default ) = default_port( &protocol ) {
+            if port == default.to_string( ) {
+                port.clear( );
+            }
+        }
+
+        let defaults = Options::default( );
+        let protocol_component = compile( Component::Protocol, &protocol, &canonicalize_protocol, defaults )?;
+        let username_component = compile( Component::Username, &username, &canonicalize_username, defaults )?;
+        let password_component = compile( Component::Password, &password, &canonicalize_password, defaults )?;
+        let hostname_component = if is_ipv6_pattern( &hostname ) {
+            compile( Component::Hostname, &hostname, &canonicalize_ipv6_hostname, Options::hostname( ) )?
+        } else {
+            compile( Component::Hostname, &hostname, &canonicalize_hostname, Options::hostname( ) )?
+        };
+        let port_component = compile( Component::Port, &port, &|p| canonicalize_port( p, None ), defaults )?;
+
+        let compile_options = Options{ ignore_case: options.ignore_case, ..defaults };
+        let pathname_component = if protocol_component.matches_special_scheme( ) {
+            compile( Component::Pathname, &pathname, &canonicalize_pathname,
+                     Options{ ignore_case: options.ignore_case, ..Options::pathname( ) } )?
+        } else {
+            compile( Component::Pathname, &pathname, &canonicalize_opaque_pathname, compile_options )?
+        };
+        let search_component = compile( Component::Search, &search, &canonicalize_search, compile_options )?;
+        let hash_component = compile( Component::Hash, &hash, &canonicalize_hash, compile_options )?;
+
+        Ok( UrlPattern{
+            protocol: protocol_component,
+            username: username_component,
+            password: password_component,
+            hostname: hostname_component,
+            port: port_component,
+            pathname: pathname_component,
+            search: search_component,
+            hash: hash_component,
+        } )
+    }
+
+    /// The normalized pattern string of the protocol component
+    pub fn protocol( &self ) -> &str {
+        &self.protocol.pattern_string
+    }
+
+    /// The normalized pattern string of the username component
+    pub fn username( &self ) -> &str {
+        &self.username.pattern_string
+    }
+
+    /// The normalized pattern string of the password component
+    pub fn password( &self ) -> &str {
+        &self.password.pattern_string
+    }
+
+    /// The normalized pattern string of the hostname component
+    pub fn hostname( &self ) -> &str {
+        &self.hostname.pattern_string
+    }
+
+    /// The normalized pattern string of the port component
+    pub fn port( &self ) -> &str {
+        &self.port.pattern_string
+    }
+
+    /// The normalized pattern string of the pathname component
+    pub fn pathname( &self ) -> &str {
+        &self.pathname.pattern_string
+    }
+
+    /// The normalized pattern string of the search component
+    pub fn search( &self ) -> &str {
+        &self.search.pattern_string
+    }
+
+    /// The normalized pattern string of the hash component
+    pub fn hash( &self ) -> &str {
+        &self.hash.pattern_string
+    }
+
+    /// Returns true if any component uses a regular expression group
+    pub fn has_regexp_groups( &self ) -> bool {
+        self.components( ).iter( ).any( |c| c.has_regexp_groups )
+    }
+
+    /// Returns true if the Url matches this pattern
+    pub fn test( &self, url:&Url ) -> bool {
+        self.exec( url ).is_some( )
+    }
+
+    /// Match a Url against this pattern, returning what each component's groups matched
+    pub fn exec( &self, url:&Url ) -> Option< UrlPatternResult > {
+        let inputs = [
+            url.scheme( ).to_owned( ),
+            url.username( ).to_owned( ),
+            url.password( ).unwrap_or( "" ).to_owned( ),
+            url.host_str( ).unwrap_or( "" ).to_owned( ),
+            url.port( ).map( |p| p.to_string( ) ).unwrap_or_default( ),
+            url.path( ).to_owned( ),
+            url.query( ).unwrap_or( "" ).to_owned( ),
+            url.fragment( ).unwrap_or( "" ).to_owned( ),
+        ];
+        self.exec_components( inputs )
+    }
+
+    /// Match Url components given separately against this pattern. The components are canonicalized
+    /// first and, if a `base_url` is given, missing components are inherited from it.
+    ///
+    /// Returns None if the components do not match or cannot be canonicalized.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use base_url::urlpattern::{ UrlPattern, UrlPatternInit, UrlPatternOptions };
+    ///
+    /// let pattern = UrlPattern::parse( "https://example.org/caf%C3%A9/:id", None, UrlPatternOptions::default( ) ).unwrap( );
+    /// let input = UrlPatternInit{
+    ///     protocol: Some( "https".to_owned( ) ),
+    ///     hostname: Some( "EXAMPLE.org".to_owned( ) ),
+    ///     pathname: Some( "/café/7".to_owned( ) ),
+    ///     ..UrlPatternInit::default( )
+    /// };
+    ///
+    /// let result = pattern.exec_init( &input ).unwrap( );
+    /// assert_eq!( result.hostname.input, "example.org" );
+    /// assert_eq!( result.pathname.group( "id" ), Some( "7" ) );
+    /// ```
+    pub fn exec_init( &self, init:&UrlPatternInit ) -> Option< UrlPatternResult > {
+        let processed = process_init( init, InitType::Url, Some( UrlPatternInit::default( ) ) ).ok( )?;
+        let inputs = [
+            processed.protocol.unwrap_or_default( ),
+            processed.username.unwrap_or_default( ),
+            processed.password.unwrap_or_default( ),
+            processed.hostname.unwrap_or_default( ),
+            processed.port.unwrap_or_default( ),
+            processed.pathname.unwrap_or_default( ),
+            processed.search.unwrap_or_default( ),
+            processed.hash.unwrap_or_default( ),
+        ];
+        self.exec_components( inputs )
+    }
+
+    fn components( &self ) -> [&CompiledComponent; 8] {
+        [ &self.protocol, &self.username, &self.password, &self.hostname,
+          &self.port, &self.pathname, &self.search, &self.hash ]
+    }
+
+    fn exec_components( &self, inputs:[String; 8] ) -> Option< UrlPatternResult > {
+        let mut results = Vec::with_capacity( 8 );
+        for ( component, input ) in self.components( ).iter( ).zip( inputs ) {
+            results.push( component.exec( input )? );
+        }
+        let mut results = results.into_iter( );
+        let mut next = || results.next( ).unwrap( );
+        Some( UrlPatternResult{
+            protocol: next( ),
+            username: next( ),
+            password: next( ),
+            hostname: next( ),
+            port: next( ),
+            pathname: next( ),
+            search: next( ),
+            hash: next( ),
+        } )
+    }
+}
+
+/*
+ * Tokenizer
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TokenType {
+    Open,
+    Close,
+    Regexp,
+    Name,
+    Char,
+    EscapedChar,
+    OtherModifier,
+    Asterisk,
+    End,
+    InvalidChar,
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenType,
+    /// Index, in characters, of the start of the token in the input
+    index: usize,
+    value: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Policy {
+    Strict,
+    Lenient,
+}
+
+fn is_valid_name_code_point( c:char, first:bool ) -> bool {
+    if first {
+        c.is_xid_start( ) || c == '$' || c == '_'
+    } else {
+        c.is_xid_continue( ) || c == '$' || c == '\u{200C}' || c == '\u{200D}'
+    }
+}
+
+struct Tokenizer<'a> {
+    input: &'a [char],
+    policy: Policy,
+    tokens: Vec< Token >,
+    index: usize,
+    next_index: usize,
+    code_point: char,
+}
+
+impl<'a> Tokenizer<'a> {
+
+    fn next_code_point( &mut self ) {
+        self.code_point = self.input[self.next_index];
+        self.next_index += 1;
+    }
+
+    fn seek_and_next_code_point( &mut self, index:usize ) {
+        self.next_index = index;
+        self.next_code_point( );
+    }
+
+    fn add_token( &mut self, kind:TokenType, next_position:usize, value_position:usize, value_length:usize ) {
+        let value = self.input[value_position..value_position + value_length].iter( ).collect( );
+        self.tokens.push( Token{ kind, index: self.index, value } );
+        self.index = next_position;
+    }
+
+    fn add_token_default_length( &mut self, kind:TokenType, next_position:usize, value_position:usize ) {
+        self.add_token( kind, next_position, value_position, next_position - value_position );
+    }
+
+    fn add_token_default_positions( &mut self, kind:TokenType ) {
+        let ( next, index ) = ( self.next_index, self.index );
+        self.add_token_default_length( kind, next, index );
+    }
+
+    fn error( &mut self, next_position:usize, value_position:usize ) -> Result< (), usize > {
+        if self.policy == Policy::Strict {
+            return Err( value_position );
+        }
+        self.add_token_default_length( TokenType::InvalidChar, next_position, value_position );
+        Ok( () )
+    }
+}
+
+fn tokenize( input:&[char], policy:Policy ) -> Result< Vec< Token >, usize > {
+    let mut t = Tokenizer{ input, policy, tokens: Vec::new( ), index: 0, next_index: 0, code_point: '\0' };
+
+    while t.index < input.len( ) {
+        t.seek_and_next_code_point( t.index );
+        match t.code_point {
+            '*' => t.add_token_default_positions( TokenType::Asterisk ),
+            '+' | '?' => t.add_token_default_positions( TokenType::OtherModifier ),
+            '\\' => {
+                if t.index == input.len( ) - 1 {
+                    let ( next, index ) = ( t.next_index, t.index );
+                    t.error( next, index )?;
+                    continue;
+                }
+                let escaped_index = t.next_index;
+                t.next_code_point( );
+                let next = t.next_index;
+                t.add_token_default_length( TokenType::EscapedChar, next, escaped_index );
+            }
+            '{' => t.add_token_default_positions( TokenType::Open ),
+            '}' => t.add_token_default_positions( TokenType::Close ),
+            ':' => {
+                let name_start = t.next_index;
+                let mut name_position = name_start;
+                while name_position < input.len( ) {
+                    t.seek_and_next_code_point( name_position );
+                    if !is_valid_name_code_point( t.code_point, name_position == name_start ) {
+                        break;
+                    }
+                    name_position = t.next_index;
+                }
+                if name_position <= name_start {
+                    let index = t.index;
+                    t.error( name_start, index )?;
+                    continue;
+                }
+                t.add_token_default_length( TokenType::Name, name_position, name_start );
+            }
+            '(' => {
+                let mut depth = 1;
+                let mut regexp_position = t.next_index;
+                let regexp_start = regexp_position;
+                let mut error = false;
+
+                while regexp_position < input.len( ) {
+                    t.seek_and_next_code_point( regexp_position );
+                    if !t.code_point.is_ascii( ) || ( regexp_position == regexp_start && t.code_point == '?' ) {
+                        let index = t.index;
+                        t.error( regexp_start, index )?;
+                        error = true;
+                        break;
+                    }
+                    if t.code_point == '\\' {
+                        if regexp_position == input.len( ) - 1 {
+                            let index = t.index;
+                            t.error( regexp_start, index )?;
+                            error = true;
+                            break;
+                        }
+                        t.next_code_point( );
+                        if !t.code_point.is_ascii( ) {
+                            let index = t.index;
+                            t.error( regexp_start, index )?;
+                            error = true;
+                            break;
+                        }
+                        regexp_position = t.next_index;
+                        continue;
+                    }
+                    if t.code_point == ')' {
+                        depth -= 1;
+                        if depth == 0 {
+                            regexp_position = t.next_index;
+                            break;
+                        }
+                    } else if t.code_point == '(' {
+                        depth += 1;
+                        if regexp_position == input.len( ) - 1 {
+                            let index = t.index;
+                            t.error( regexp_start, index )?;
+                            error = true;
+                            break;
+                        }
+                        let temporary_position = t.next_index;
+                        t.next_code_point( );
+                        if t.code_point != '?' {
+                            let index = t.index;
+                            t.error( regexp_start, index )?;
+                            error = true;
+                            break;
+                        }
+                        t.next_index = temporary_position;
+                    }
+                    regexp_position = t.next_index;
+                }
+
+                if error {
+                    continue;
+                }
+                if depth != 0 {
+                    let index = t.index;
+                    t.error( regexp_start, index )?;
+                    continue;
+                }
+                let regexp_length = regexp_position - regexp_start - 1;
+                if regexp_length == 0 {
+                    let index = t.index;
+                    t.error( regexp_start, index )?;
+                    continue;
+                }
+                t.add_token( TokenType::Regexp, regexp_position, regexp_start, regexp_length );
+            }
+            _ => t.add_token_default_positions( TokenType::Char ),
+        }
+    }
+
+    let index = t.index;
+    t.add_token_default_length( TokenType::End, index, index );
+    Ok( t.tokens )
+}
+
+/*
+ * Pattern parser
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PartType {
+    FixedText,
+    Regexp,
+    SegmentWildcard,
+    FullWildcard,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Modifier {
+    None,
+    Optional,
+    ZeroOrMore,
+    OneOrMore,
+}
+
+impl Modifier {
+    fn as_str( self ) -> &'static str {
+        match self {
+            Modifier::None => "",
+            Modifier::Optional => "?",
+            Modifier::ZeroOrMore => "*",
+            Modifier::OneOrMore => "+",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Part {
+    kind: PartType,
+    value: String,
+    modifier: Modifier,
+    name: String,
+    prefix: String,
+    suffix: String,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Options {
+    delimiter: Option< char >,
+    prefix: Option< char >,
+    ignore_case: bool,
+}
+
+impl Options {
+
+    fn hostname( ) -> Self {
+        Options{ delimiter: Some( '.' ), prefix: None, ignore_case: false }
+    }
+
+    fn pathname( ) -> Self {
+        Options{ delimiter: Some( '/' ), prefix: Some( '/' ), ignore_case: false }
+    }
+
+    /// The segment wildcard as written by the standard, used when comparing against regexp groups
+    /// and writing pattern strings
+    fn segment_wildcard( &self ) -> String {
+        let mut delimiter = String::new( );
+        if let Some( d ) = self.delimiter {
+            escape_regexp_into( &mut delimiter, &d.to_string( ) );
+        }
+        format!( "[^{}]+?", delimiter )
+    }
+
+    /// The segment wildcard as understood by the regex crate, which has no `[^]`
+    fn segment_wildcard_regex( &self ) -> String {
+        match self.delimiter {
+            Some( _ ) => self.segment_wildcard( ),
+            None => "(?s:.)+?".to_owned( ),
+        }
+    }
+}
+
+const FULL_WILDCARD:&str = ".*";
+
+type Encoder<'a> = &'a dyn Fn( &str ) -> Result< String, () >;
+
+struct PatternParser<'a> {
+    tokens: Vec< Token >,
+    encoder: Encoder<'a>,
+    segment_wildcard: String,
+    parts: Vec< Part >,
+    pending_fixed_value: String,
+    index: usize,
+    next_numeric_name: usize,
+}
+
+enum ParseError {
+    Syntax( usize ),
+    Duplicate( String ),
+    Encoding( String ),
+}
+
+impl<'a> PatternParser<'a> {
+
+    fn try_consume( &mut self, kind:TokenType ) -> Option< Token > {
+        if self.tokens[self.index].kind != kind {
+            return None;
+        }
+        self.index += 1;
+        Some( self.tokens[self.index - 1].clone( ) )
+    }
+
+    fn try_consume_modifier( &mut self ) -> Option< Token > {
+        self.try_consume( TokenType::OtherModifier ).or_else( || self.try_consume( TokenType::Asterisk ) )
+    }
+
+    fn try_consume_regexp_or_wildcard( &mut self, name:&Option< Token > ) -> Option< Token > {
+        let token = self.try_consume( TokenType::Regexp );
+        if name.is_none( ) && token.is_none( ) {
+            return self.try_consume( TokenType::Asterisk );
+        }
+        token
+    }
+
+    fn consume_required( &mut self, kind:TokenType ) -> Result< Token, ParseError > {
+        let index = self.tokens[self.index].index;
+        self.try_consume( kind ).ok_or( ParseError::Syntax( index ) )
+    }
+
+    fn consume_text( &mut self ) -> String {
+        let mut result = String::new( );
+        while let Some( token ) = self.try_consume( TokenType::Char ).or_else( || self.try_consume( TokenType::EscapedChar ) ) {
+            result.push_str( &token.value );
+        }
+        result
+    }
+
+    fn encode( &self, value:&str ) -> Result< String, ParseError > {
+        ( self.encoder )( value ).map_err( |_| ParseError::Encoding( value.to_owned( ) ) )
+    }
+
+    fn maybe_add_part_from_pending( &mut self ) -> Result< (), ParseError > {
+        if self.pending_fixed_value.is_empty( ) {
+            return Ok( () );
+        }
+        let value = self.encode( &self.pending_fixed_value )?;
+        self.pending_fixed_value.clear( );
+        self.parts.push( Part{ kind: PartType::FixedText, value, modifier: Modifier::None,
+                               name: String::new( ), prefix: String::new( ), suffix: String::new( ) } );
+        Ok( () )
+    }
+
+    fn add_part( &mut self, prefix:&str, name_token:Option< Token >, regexp_or_wildcard:Option< Token >,
+                 suffix:&str, modifier_token:Option< Token > ) -> Result< (), ParseError > {
+        let modifier = match modifier_token.as_ref( ).map( |t| t.value.as_str( ) ) {
+            Some( "?" ) => Modifier::Optional,
+            Some( "*" ) => Modifier::ZeroOrMore,
+            Some( "+" ) => Modifier::OneOrMore,
+            _ => Modifier::None,
+        };
+        if name_token.is_none( ) && regexp_or_wildcard.is_none( ) && modifier == Modifier::None {
+            self.pending_fixed_value.push_str( prefix );
+            return Ok( () );
+        }
+        self.maybe_add_part_from_pending( )?;
+        if name_token.is_none( ) && regexp_or_wildcard.is_none( ) {
+            if prefix.is_empty( ) {
+                return Ok( () );
+            }
+            let value = self.encode( prefix )?;
+            self.parts.push( Part{ kind: PartType::FixedText, value, modifier,
+                                   name: String::new( ), prefix: String::new( ), suffix: String::new( ) } );
+            return Ok( () );
+        }
+
+        let mut regexp_value = match &regexp_or_wildcard {
+            None => self.segment_wildcard.clone( ),
+            Some( t ) if t.kind == TokenType::Asterisk => FULL_WILDCARD.to_owned( ),
+            Some( t ) => t.value.clone( ),
+        };
+        let mut kind = PartType::Regexp;
+        if regexp_value == self.segment_wildcard {
+            kind = PartType::SegmentWildcard;
+            regexp_value.clear( );
+        } else if regexp_value == FULL_WILDCARD {
+            kind = PartType::FullWildcard;
+            regexp_value.clear( );
+        }
+
+        let name = match name_token {
+            Some( t ) => t.value,
+            None => {
+                self.next_numeric_name += 1;
+                ( self.next_numeric_name - 1 ).to_string( )
+            }
+        };
+        if self.parts.iter( ).any( |p| p.name == name ) {
+            return Err( ParseError::Duplicate( name ) );
+        }
+
+        let prefix = self.encode( prefix )?;
+        let suffix = self.encode( suffix )?;
+        self.parts.push( Part{ kind, value: regexp_value, modifier, name, prefix, suffix } );
+        Ok( () )
+    }
+}
+
+fn parse_pattern_string( input:&str, options:Options, encoder:Encoder ) -> Result< Vec< Part >, ParseError > {
+    let chars:Vec< char > = input.chars( ).collect( );
+    let tokens = tokenize( &chars, Policy::Strict ).map_err( ParseError::Syntax )?;
+    let mut p = PatternParser{
+        tokens,
+        encoder,
+        segment_wildcard: options.segment_wildcard( ),
+        parts: Vec::new( ),
+        pending_fixed_value: String::new( ),
+        index: 0,
+        next_numeric_name: 0,
+    };
+    let prefix_code_point = options.prefix.map( |c| c.to_string( ) ).unwrap_or_default( );
+
+    while p.index < p.tokens.len( ) {
+        let char_token = p.try_consume( TokenType::Char );
+        let name_token = p.try_consume( TokenType::Name );
+        let regexp_or_wildcard = p.try_consume_regexp_or_wildcard( &name_token );
+
+        if name_token.is_some( ) || regexp_or_wildcard.is_some( ) {
+            let mut prefix = char_token.map( |t| t.value ).unwrap_or_default( );
+            if !prefix.is_empty( ) && prefix != prefix_code_point {
+                p.pending_fixed_value.push_str( &prefix );
+                prefix.clear( );
+            }
+            p.maybe_add_part_from_pending( )?;
+            let modifier = p.try_consume_modifier( );
+            p.add_part( &prefix, name_token, regexp_or_wildcard, "", modifier )?;
+            continue;
+        }
+
+        let fixed = char_token.or_else( || p.try_consume( TokenType::EscapedChar ) );
+        if let Some( fixed ) = fixed {
+            p.pending_fixed_value.push_str( &fixed.value );
+            continue;
+        }
+
+        if p.try_consume( TokenType::Open ).is_some( ) {
+            let prefix = p.consume_text( );
+            let name_token = p.try_consume( TokenType::Name );
+            let regexp_or_wildcard = p.try_consume_regexp_or_wildcard( &name_token );
+            let suffix = p.consume_text( );
+            p.consume_required( TokenType::Close )?;
+            let modifier = p.try_consume_modifier( );
+            p.add_part( &prefix, name_token, regexp_or_wildcard, &suffix, modifier )?;
+            continue;
+        }
+
+        p.maybe_add_part_from_pending( )?;
+        p.consume_required( TokenType::End )?;
+    }
+
+    Ok( p.parts )
+}
+
+/*
+ * Compilation
+ */
+
+fn escape_regexp_into( out:&mut String, input:&str ) {
+    for c in input.chars( ) {
+        if ".+*?^${}()[]|/\\".contains( c ) {
+            out.push( '\\' );
+        }
+        out.push( c );
+    }
+}
+
+fn escape_pattern_string( input:&str ) -> String {
+    let mut out = String::with_capacity( input.len( ) );
+    for c in input.chars( ) {
+        if "+*?:{}()\\".contains( c ) {
+            out.push( '\\' );
+        }
+        out.push( c );
+    }
+    out
+}
+
+fn generate_regex( parts:&[Part], options:&Options ) -> String {
+    let mut result = String::from( if options.ignore_case { "(?i)^" } else { "^" } );
+    for part in parts {
+        if part.kind == PartType::FixedText {
+            if part.modifier == Modifier::None {
+                escape_regexp_into( &mut result, &part.value );
+            } else {
+                result.push_str( "(?:" );
+                escape_regexp_into( &mut result, &part.value );
+                result.push( ')' );
+                result.push_str( part.modifier.as_str( ) );
+            }
+            continue;
+        }
+
+        let regexp_value = match part.kind {
+            PartType::SegmentWildcard => options.segment_wildcard_regex( ),
+            PartType::FullWildcard => FULL_WILDCARD.to_owned( ),
+            _ => part.value.clone( ),
+        };
+        let single = part.modifier == Modifier::None || part.modifier == Modifier::Optional;
+
+        if part.prefix.is_empty( ) && part.suffix.is_empty( ) {
+            if single {
+                result.push_str( &format!( "({}){}", regexp_value, part.modifier.as_str( ) ) );
+            } else {
+                result.push_str( &format!( "((?:{}){})", regexp_value, part.modifier.as_str( ) ) );
+            }
+            continue;
+        }
+
+        let mut prefix = String::new( );
+        escape_regexp_into( &mut prefix, &part.prefix );
+        let mut suffix = String::new( );
+        escape_regexp_into( &mut suffix, &part.suffix );
+
+        if single {
+            result.push_str( &format!( "(?:{}({}){}){}", prefix, regexp_value, suffix, part.modifier.as_str( ) ) );
+            continue;
+        }
+        result.push_str( &format!( "(?:{p}((?:{r})(?:{s}{p}(?:{r}))*){s})", p = prefix, r = regexp_value, s = suffix ) );
+        if part.modifier == Modifier::ZeroOrMore {
+            result.push( '?' );
+        }
+    }
+    result.push( '$' );
+    result
+}
+
+fn generate_pattern_string( parts:&[Part], options:&Options ) -> String {
+    let mut result = String::new( );
+    let prefix_code_point = options.prefix.map( |c| c.to_string( ) ).unwrap_or_default( );
+
+    for ( index, part ) in parts.iter( ).enumerate( ) {
+        let previous = if index > 0 { parts.get( index - 1 ) } else { None };
+        let next = parts.get( index + 1 );
+
+        if part.kind == PartType::FixedText {
+            if part.modifier == Modifier::None {
+                result.push_str( &escape_pattern_string( &part.value ) );
+            } else {
+                result.push_str( &format!( "{{{}}}{}", escape_pattern_string( &part.value ), part.modifier.as_str( ) ) );
+            }
+            continue;
+        }
+
+        let custom_name = !part.name.starts_with( |c:char| c.is_ascii_digit( ) );
+        let mut needs_grouping = !part.suffix.is_empty( )
+            || ( !part.prefix.is_empty( ) && part.prefix != prefix_code_point );
+
+        if !needs_grouping && custom_name && part.kind == PartType::SegmentWildcard && part.modifier == Modifier::None {
+            if let Some( next ) = next {
+                if next.prefix.is_empty( ) && next.suffix.is_empty( ) {
+                    needs_grouping = if next.kind == PartType::FixedText {
+                        next.value.chars( ).next( ).is_some_and( |c| is_valid_name_code_point( c, false ) )
+                    } else {
+                        next.name.starts_with( |c:char| c.is_ascii_digit( ) )
+                    };
+                }
+            }
+        }
+
+        if !needs_grouping && part.prefix.is_empty( ) {
+            if let Some( previous ) = previous {
+                if previous.kind == PartType::FixedText && options.prefix.is_some( )
+                    && previous.value.ends_with( &prefix_code_point[..] ) {
+                    needs_grouping = true;
+                }
+            }
+        }
+
+        if needs_grouping {
+            result.push( '{' );
+        }
+        result.push_str( &escape_pattern_string( &part.prefix ) );
+        if custom_name {
+            result.push( ':' );
+            result.push_str( &part.name );
+        }
+        match part.kind {
+            PartType::Regexp => result.push_str( &format!( "({})", part.value ) ),
+            PartType::SegmentWildcard if !custom_name => {
+                result.push_str( &format!( "({})", options.segment_wildcard( ) ) );
+            }
+            PartType::FullWildcard => {
+                let star = !custom_name && ( previous.is_none_or( |p| p.kind == PartType::FixedText || p.modifier != Modifier::None )
+                                             || needs_grouping || !part.prefix.is_empty( ) );
+                if star {
+                    result.push( '*' );
+                } else {
+                    result.push_str( &format!( "({})", FULL_WILDCARD ) );
+                }
+            }
+            _ => {}
+        }
+        if part.kind == PartType::SegmentWildcard && custom_name
+            && part.suffix.chars( ).next( ).is_some_and( |c| is_valid_name_code_point( c, false ) ) {
+            result.push( '\\' );
+        }
+        result.push_str( &escape_pattern_string( &part.suffix ) );
+        if needs_grouping {
+            result.push( '}' );
+        }
+        result.push_str( part.modifier.as_str( ) );
+    }
+    result
+}
+
+#[derive(Clone, Debug)]
+struct CompiledComponent {
+    pattern_string: String,
+    regex: Regex,
+    group_names: Vec< String >,
+    has_regexp_groups: bool,
+}
+
+impl CompiledComponent {
+
+    fn exec( &self, input:String ) -> Option< ComponentResult > {
+        let groups = {
+            let captures = self.regex.captures( &input )?;
+            self.group_names.iter( )
+                            .enumerate( )
+                            .map( |( i, name )| ( name.clone( ), captures.get( i + 1 ).map( |m| m.as_str( ).to_owned( ) ) ) )
+                            .collect( )
+        };
+        Some( ComponentResult{ input, groups } )
+    }
+
+    fn matches_special_scheme( &self ) -> bool {
+        SPECIAL_SCHEMES.iter( ).any( |scheme| self.regex.is_match( scheme ) )
+    }
+}
+
+fn compile( component:Component, input:&str, encoder:Encoder, options:Options ) -> Result< CompiledComponent, UrlPatternError > {
+    let parts = parse_pattern_string( input, options, encoder ).map_err( |e| match e {
+        ParseError::Syntax( index ) => UrlPatternError::Syntax( component, index ),
+        ParseError::Duplicate( name ) => UrlPatternError::DuplicateName( component, name ),
+        ParseError::Encoding( text ) => UrlPatternError::InvalidText( component, text ),
+    } )?;
+    let regex = Regex::new( &generate_regex( &parts, &options ) )
+                      .map_err( |e| UrlPatternError::Regex( component, e.to_string( ) ) )?;
+    Ok( CompiledComponent{
+        pattern_string: generate_pattern_string( &parts, &options ),
+        regex,
+        group_names: parts.iter( ).filter( |p| p.kind != PartType::FixedText ).map( |p| p.name.clone( ) ).collect( ),
+        has_regexp_groups: parts.iter( ).any( |p| p.kind == PartType::Regexp ),
+    } )
+}
+
+/*
+ * Canonicalization
+ */
+
+const SPECIAL_SCHEMES:[&str; 6] = [ "ftp", "file", "http", "https", "ws", "wss" ];
+
+fn default_port( scheme:&str ) -> Option< u16 > {
+    match scheme {
+        "ftp" => Some( 21 ),
+        "http" | "ws" => Some( 80 ),
+        "https" | "wss" => Some( 443 ),
+        _ => None,
+    }
+}
+
+fn dummy_url( ) -> Url {
+    Url::parse( "https://dummy.invalid/" ).unwrap( )
+}
+
+fn canonicalize_protocol( value:&str ) -> Result< String, () > {
+    if value.is_empty( ) {
+        return Ok( String::new( ) );
+    }
+    match Url::parse( &format!( "{}://dummy.invalid/", value ) ) {
+        Ok( url ) if url.scheme( ) == value.to_ascii_lowercase( ) => Ok( url.scheme( ).to_owned( ) ),
+        _ => Err( () ),
+    }
+}
+
+fn canonicalize_username( value:&str ) -> Result< String, () > {
+    if value.is_empty( ) {
+        return Ok( String::new( ) );
+    }
+    let mut url = dummy_url( );
+    url.set_username( value )?;
+    Ok( url.username( ).to_owned( ) )
+}
+
+fn canonicalize_password( value:&str ) -> Result< String, () > {
+    if value.is_empty( ) {
+        return Ok( String::new( ) );
+    }
+    let mut url = dummy_url( );
+    url.set_password( Some( value ) )?;
+    Ok( url.password( ).unwrap_or( "" ).to_owned( ) )
+}
+
+fn canonicalize_hostname( value:&str ) -> Result< String, () > {
+    if value.is_empty( ) {
+        return Ok( String::new( ) );
+    }
+    // Setting a hostname stops at the start of the next component and ignores the rest, though a
+    // port may not be set this way
+    let mut hostname = String::with_capacity( value.len( ) );
+    let mut in_brackets = false;
+    for c in value.chars( ) {
+        match c {
+            '\t' | '\n' | '\r' => continue,
+            '/' | '?' | '#' | '\\' => break,
+            ':' if !in_brackets => return Err( () ),
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            _ => {}
+        }
+        hostname.push( c );
+    }
+    if hostname.contains( [ '<', '>', '^', '|', ' ', '@', '%', '\u{7F}' ] ) || hostname.contains( char::is_control ) {
+        return Err( () );
+    }
+    let mut url = dummy_url( );
+    url.set_host( Some( &hostname ) ).map_err( |_| () )?;
+    Ok( url.host_str( ).unwrap_or( "" ).to_owned( ) )
+}
+
+fn canonicalize_ipv6_hostname( value:&str ) -> Result< String, () > {
+    let mut result = String::with_capacity( value.len( ) );
+    for c in value.chars( ) {
+        if !c.is_ascii_hexdigit( ) && c != '[' && c != ']' && c != ':' {
+            return Err( () );
+        }
+        result.push( c.to_ascii_lowercase( ) );
+    }
+    Ok( result )
+}
+
+fn canonicalize_port( value:&str, protocol:Option< &str > ) -> Result< String, () > {
+    if value.is_empty( ) {
+        return Ok( String::new( ) );
+    }
+    let value:String = value.chars( ).filter( |c| !matches!( c, '\t' | '\n' | '\r' ) ).collect( );
+    let digits:String = value.chars( ).take_while( |c| c.is_ascii_digit( ) ).collect( );
+    if digits.is_empty( ) {
+        return Err( () );
+    }
+    let port:u16 = digits.parse( ).map_err( |_| () )?;
+    if protocol.and_then( default_port ) == Some( port ) {
+        return Ok( String::new( ) );
+    }
+    Ok( port.to_string( ) )
+}
+
+fn canonicalize_pathname( value:&str ) -> Result< String, () > {
+    if value.is_empty( ) {
+        return Ok( String::new( ) );
+    }
+    let leading_slash = value.starts_with( '/' );
+    let modified = if leading_slash { value.to_owned( ) } else { format!( "/-{}", value ) };
+    let mut url = dummy_url( );
+    url.set_path( &modified );
+    let path = url.path( );
+    Ok( if leading_slash { path.to_owned( ) } else { path[2..].to_owned( ) } )
+}
+
+fn canonicalize_opaque_pathname( value:&str ) -> Result< String, () > {
+    Ok( utf8_percent_encode( value, SIMPLE_ENCODE_SET ).collect( ) )
+}
+
+fn canonicalize_search( value:&str ) -> Result< String, () > {
+    if value.is_empty( ) {
+        return Ok( String::new( ) );
+    }
+    let mut url = dummy_url( );
+    url.set_query( Some( value ) );
+    Ok( url.query( ).unwrap_or( "" ).to_owned( ) )
+}
+
+fn canonicalize_hash( value:&str ) -> Result< String, () > {
+    if value.is_empty( ) {
+        return Ok( String::new( ) );
+    }
+    let mut url = dummy_url( );
+    url.set_fragment( Some( value ) );
+    Ok( url.fragment( ).unwrap_or( "" ).to_owned( ) )
+}
+
+fn is_ipv6_pattern( value:&str ) -> bool {
+    value.starts_with( '[' ) || value.starts_with( "{[" ) || value.starts_with( "\\[" )
+}
+
+/*
+ * URLPatternInit processing
+ */
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum InitType {
+    Pattern,
+    Url,
+}
+
+fn process_base_string( value:&str, kind:InitType ) -> String {
+    match kind {
+        InitType::Pattern => escape_pattern_string( value ),
+        InitType::Url => value.to_owned( ),
+    }
+}
+
+fn is_absolute_pathname( value:&str, kind:InitType ) -> bool {
+    let mut chars = value.chars( );
+    match ( chars.next( ), chars.next( ) ) {
+        ( Some( '/' ), _ ) => true,
+        _ if kind == InitType::Url => false,
+        ( Some( '\\' ), Some( '/' ) ) | ( Some( '{' ), Some( '/' ) ) => true,
+        _ => false,
+    }
+}
+
+/// Process an init for use as a pattern, or as a Url to match when `defaults` is given. Components
+/// are canonicalized in the latter case.
+fn process_init( init:&UrlPatternInit, kind:InitType, defaults:Option< UrlPatternInit > )
+                 -> Result< UrlPatternInit, UrlPatternError > {
+    let mut result = defaults.unwrap_or_default( );
+    let invalid = |component, value:&str| UrlPatternError::InvalidText( component, value.to_owned( ) );
+
+    if let Some( base ) = &init.base_url {
+        let base = &base.url;
+        let has = |fields:&[&Option< String >]| fields.iter( ).any( |f| f.is_some( ) );
+
+        if init.protocol.is_none( ) {
+            result.protocol = Some( process_base_string( base.scheme( ), kind ) );
+        }
+        if kind != InitType::Pattern && !has( &[ &init.protocol, &init.hostname, &init.port, &init.username ] ) {
+            result.username = Some( process_base_string( base.username( ), kind ) );
+        }
+        if kind != InitType::Pattern && !has( &[ &init.protocol, &init.hostname, &init.port, &init.username, &init.password ] ) {
+            result.password = Some( process_base_string( base.password( ).unwrap_or( "" ), kind ) );
+        }
+        if !has( &[ &init.protocol, &init.hostname ] ) {
+            result.hostname = Some( process_base_string( base.host_str( ).unwrap_or( "" ), kind ) );
+        }
+        if !has( &[ &init.protocol, &init.hostname, &init.port ] ) {
+            result.port = Some( base.port( ).map( |p| p.to_string( ) ).unwrap_or_default( ) );
+        }
+        if !has( &[ &init.protocol, &init.hostname, &init.port, &init.pathname ] ) {
+            result.pathname = Some( process_base_string( base.path( ), kind ) );
+        }
+        if !has( &[ &init.protocol, &init.hostname, &init.port, &init.pathname, &init.search ] ) {
+            result.search = Some( process_base_string( base.query( ).unwrap_or( "" ), kind ) );
+        }
+        if !has( &[ &init.protocol, &init.hostname, &init.port, &init.pathname, &init.search, &init.hash ] ) {
+            result.hash = Some( process_base_string( base.fragment( ).unwrap_or( "" ), kind ) );
+        }
+    }
+
+    let canonical = |component, value:&str, f:&dyn Fn( &str ) -> Result< String, () >| -> Result< String, UrlPatternError > {
+        if kind == InitType::Pattern {
+            Ok( value.to_owned( ) )
+        } else {
+            f( value ).map_err( |_| invalid( component, value ) )
+        }
+    };
+
+    if let Some( protocol ) = &init.protocol {
+        let stripped = protocol.strip_suffix( ':' ).unwrap_or( protocol );
+        result.protocol = Some( canonical( Component::Protocol, stripped, &canonicalize_protocol )? );
+    }
+    if let Some( username ) = &init.username {
+        result.username = Some( canonical( Component::Username, username, &canonicalize_username )? );
+    }
+    if let Some( password ) = &init.password {
+        result.password = Some( canonical( Component::Password, password, &canonicalize_password )? );
+    }
+    if let Some( hostname ) = &init.hostname {
+        result.hostname = Some( canonical( Component::Hostname, hostname, &canonicalize_hostname )? );
+    }
+    if let Some( port ) = &init.port {
+        let protocol = result.protocol.clone( );
+        result.port = Some( canonical( Component::Port, port, &|p| canonicalize_port( p, protocol.as_deref( ) ) )? );
+    }
+    if let Some( pathname ) = &init.pathname {
+        let mut pathname = pathname.clone( );
+        if let Some( base ) = &init.base_url {
+            if !is_absolute_pathname( &pathname, kind ) {
+                let base_path = process_base_string( base.path( ), kind );
+                if let Some( slash ) = base_path.rfind( '/' ) {
+                    pathname = format!( "{}{}", &base_path[..=slash], pathname );
+                }
+            }
+        }
+        let protocol = result.protocol.clone( ).unwrap_or_default( );
+        let special = protocol.is_empty( ) || SPECIAL_SCHEMES.contains( &protocol.as_str( ) );
+        result.pathname = Some( if special {
+            canonical( Component::Pathname, &pathname, &canonicalize_pathname )?
+        } else {
+            canonical( Component::Pathname, &pathname, &canonicalize_opaque_pathname )?
+        } );
+    }
+    if let Some( search ) = &init.search {
+        let stripped = search.strip_prefix( '?' ).unwrap_or( search );
+        result.search = Some( canonical( Component::Search, stripped, &canonicalize_search )? );
+    }
+    if let Some( hash ) = &init.hash {
+        let stripped = hash.strip_prefix( '#' ).unwrap_or( hash );
+        result.hash = Some( canonical( Component::Hash, stripped, &canonicalize_hash )? );
+    }
+
+    Ok( result )
+}
+
+/*
+ * Constructor string parser
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Init,
+    Protocol,
+    Authority,
+    Username,
+    Password,
+    Hostname,
+    Port,
+    Pathname,
+    Search,
+    Hash,
+    Done,
+}
+
+struct ConstructorParser {
+    input: Vec< char >,
+    tokens: Vec< Token >,
+    result: UrlPatternInit,
+    component_start: usize,
+    token_index: usize,
+    token_increment: usize,
+    group_depth: usize,
+    ipv6_bracket_depth: usize,
+    protocol_matches_special_scheme: bool,
+    state: State,
+}
+
+impl ConstructorParser {
+
+    fn safe_token( &self, index:usize ) -> &Token {
+        self.tokens.get( index ).unwrap_or_else( || self.tokens.last( ).unwrap( ) )
+    }
+
+    fn is_non_special_pattern_char( &self, index:usize, value:&str ) -> bool {
+        let token = self.safe_token( index );
+        token.value == value
+            && matches!( token.kind, TokenType::Char | TokenType::EscapedChar | TokenType::InvalidChar )
+    }
+
+    fn is_search_prefix( &self ) -> bool {
+        if self.is_non_special_pattern_char( self.token_index, "?" ) {
+            return true;
+        }
+        if self.tokens[self.token_index].value != "?" {
+            return false;
+        }
+        if self.token_index == 0 {
+            return true;
+        }
+        let previous = self.safe_token( self.token_index - 1 );
+        !matches!( previous.kind, TokenType::Name | TokenType::Regexp | TokenType::Close | TokenType::Asterisk )
+    }
+
+    fn component_string( &self ) -> String {
+        let start = self.safe_token( self.component_start ).index;
+        let end = self.tokens[self.token_index].index;
+        self.input[start..end].iter( ).collect( )
+    }
+
+    fn rewind( &mut self ) {
+        self.token_index = self.component_start;
+        self.token_increment = 0;
+    }
+
+    fn rewind_and_set_state( &mut self, state:State ) {
+        self.rewind( );
+        self.state = state;
+    }
+
+    fn change_state( &mut self, new_state:State, skip:usize ) {
+        use self::State::*;
+
+        let value = self.component_string( );
+        match self.state {
+            Protocol => self.result.protocol = Some( value ),
+            Username => self.result.username = Some( value ),
+            Password => self.result.password = Some( value ),
+            Hostname => self.result.hostname = Some( value ),
+            Port => self.result.port = Some( value ),
+            Pathname => self.result.pathname = Some( value ),
+            Search => self.result.search = Some( value ),
+            Hash => self.result.hash = Some( value ),
+            Init | Authority | Done => {}
+        }
+
+        if self.state != Init && new_state != Done {
+            if matches!( self.state, Protocol | Authority | Username | Password )
+                && matches!( new_state, Port | Pathname | Search | Hash )
+                && self.result.hostname.is_none( ) {
+                self.result.hostname = Some( String::new( ) );
+            }
+            if matches!( self.state, Protocol | Authority | Username | Password | Hostname | Port )
+                && matches!( new_state, Search | Hash )
+                && self.result.pathname.is_none( ) {
+                self.result.pathname = Some( if self.protocol_matches_special_scheme { "/".to_owned( ) } else { String::new( ) } );
+            }
+            if matches!( self.state, Protocol | Authority | Username | Password | Hostname | Port | Pathname )
+                && new_state == Hash
+                && self.result.search.is_none( ) {
+                self.result.search = Some( String::new( ) );
+            }
+        }
+
+        self.state = new_state;
+        self.token_index += skip;
+        self.component_start = self.token_index;
+        self.token_increment = 0;
+    }
+
+    fn compute_protocol_matches_special_scheme( &mut self ) -> Result< (), UrlPatternError > {
+        let protocol = self.component_string( );
+        let component = compile( Component::Protocol, &protocol, &canonicalize_protocol, Options::default( ) )?;
+        self.protocol_matches_special_scheme = component.matches_special_scheme( );
+        Ok( () )
+    }
+}
+
+fn parse_constructor_string( input:&str ) -> Result< UrlPatternInit, UrlPatternError > {
+    use self::State::*;
+
+    let chars:Vec< char > = input.chars( ).collect( );
+    // The lenient policy never fails
+    let tokens = tokenize( &chars, Policy::Lenient ).unwrap_or_default( );
+    let mut p = ConstructorParser{
+        input: chars,
+        tokens,
+        result: UrlPatternInit::default( ),
+        component_start: 0,
+        token_index: 0,
+        token_increment: 1,
+        group_depth: 0,
+        ipv6_bracket_depth: 0,
+        protocol_matches_special_scheme: false,
+        state: Init,
+    };
+
+    while p.token_index < p.tokens.len( ) {
+        p.token_increment = 1;
+
+        if p.tokens[p.token_index].kind == TokenType::End {
+            if p.state == Init {
+                p.rewind( );
+                if p.is_non_special_pattern_char( p.token_index, "#" ) {
+                    p.change_state( Hash, 1 );
+                } else if p.is_search_prefix( ) {
+                    p.change_state( Search, 1 );
+                } else {
+                    p.change_state( Pathname, 0 );
+                }
+                p.token_index += p.token_increment;
+                continue;
+            }
+            if p.state == Authority {
+                p.rewind_and_set_state( Hostname );
+                p.token_index += p.token_increment;
+                continue;
+            }
+            p.change_state( Done, 0 );
+            break;
+        }
+
+        if p.tokens[p.token_index].kind == TokenType::Open {
+            p.group_depth += 1;
+            p.token_index += p.token_increment;
+            continue;
+        }
+        if p.group_depth > 0 {
+            if p.tokens[p.token_index].kind == TokenType::Close {
+                p.group_depth -= 1;
+            } else {
+                p.token_index += p.token_increment;
+                continue;
+            }
+        }
+
+        let index = p.token_index;
+        match p.state {
+            Init => {
+                if p.is_non_special_pattern_char( index, ":" ) {
+                    p.rewind_and_set_state( Protocol );
+                }
+            }
+            Protocol => {
+                if p.is_non_special_pattern_char( index, ":" ) {
+                    p.compute_protocol_matches_special_scheme( )?;
+                    let mut next_state = Pathname;
+                    let mut skip = 1;
+                    if p.is_non_special_pattern_char( index + 1, "/" ) && p.is_non_special_pattern_char( index + 2, "/" ) {
+                        next_state = Authority;
+                        skip = 3;
+                    } else if p.protocol_matches_special_scheme {
+                        next_state = Authority;
+                    }
+                    p.change_state( next_state, skip );
+                }
+            }
+            Authority => {
+                if p.is_non_special_pattern_char( index, "@" ) {
+                    p.rewind_and_set_state( Username );
+                } else if p.is_non_special_pattern_char( index, "/" ) || p.is_search_prefix( )
+                    || p.is_non_special_pattern_char( index, "#" ) {
+                    p.rewind_and_set_state( Hostname );
+                }
+            }
+            Username => {
+                if p.is_non_special_pattern_char( index, ":" ) {
+                    p.change_state( Password, 1 );
+                } else if p.is_non_special_pattern_char( index, "@" ) {
+                    p.change_state( Hostname, 1 );
+                }
+            }
+            Password => {
+                if p.is_non_special_pattern_char( index, "@" ) {
+                    p.change_state( Hostname, 1 );
+                }
+            }
+            Hostname => {
+                if p.is_non_special_pattern_char( index, "[" ) {
+                    p.ipv6_bracket_depth += 1;
+                } else if p.is_non_special_pattern_char( index, "]" ) {
+                    p.ipv6_bracket_depth = p.ipv6_bracket_depth.saturating_sub( 1 );
+                } else if p.is_non_special_pattern_char( index, ":" ) && p.ipv6_bracket_depth == 0 {
+                    p.change_state( Port, 1 );
+                } else if p.is_non_special_pattern_char( index, "/" ) {
+                    p.change_state( Pathname, 0 );
+                } else if p.is_search_prefix( ) {
+                    p.change_state( Search, 1 );
+                } else if p.is_non_special_pattern_char( index, "#" ) {
+                    p.change_state( Hash, 1 );
+                }
+            }
+            Port => {
+                if p.is_non_special_pattern_char( index, "/" ) {
+                    p.change_state( Pathname, 0 );
+                } else if p.is_search_prefix( ) {
+                    p.change_state( Search, 1 );
+                } else if p.is_non_special_pattern_char( index, "#" ) {
+                    p.change_state( Hash, 1 );
+                }
+            }
+            Pathname => {
+                if p.is_search_prefix( ) {
+                    p.change_state( Search, 1 );
+                } else if p.is_non_special_pattern_char( index, "#" ) {
+                    p.change_state( Hash, 1 );
+                }
+            }
+            Search => {
+                if p.is_non_special_pattern_char( index, "#" ) {
+                    p.change_state( Hash, 1 );
+                }
+            }
+            Hash | Done => {}
+        }
+        p.token_index += p.token_increment;
+    }
+
+    if p.result.hostname.is_some( ) && p.result.port.is_none( ) {
+        p.result.port = Some( String::new( ) );
+    }
+    Ok( p.result )
+}
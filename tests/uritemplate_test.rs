@@ -0,0 +1,98 @@
+//! Runs the uritemplate-test conformance data from commit fdd5d611 of
+//! https://github.com/uri-templates/uritemplate-test (spec-examples.json, spec-examples-by-section.json,
+//! extended-tests.json and negative-tests.json), followed by further malformed templates which must
+//! fail to parse.
+
+use base_url::uri_template::{ TemplateError, UriTemplate, Value as TemplateValue };
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Convert a JSON variable, dropping nulls which stand for undefined variables
+fn to_value( value:&Value ) -> Option< TemplateValue > {
+    let scalar = |value:&Value| match value {
+        Value::String( s ) => s.clone( ),
+        other => other.to_string( ),
+    };
+    match value {
+        Value::Null => None,
+        Value::Array( items ) => Some( TemplateValue::List( items.iter( ).map( scalar ).collect( ) ) ),
+        Value::Object( entries ) => Some( TemplateValue::Map( entries.iter( ).map( |( k, v )| ( k.clone( ), scalar( v ) ) ).collect( ) ) ),
+        other => Some( TemplateValue::String( scalar( other ) ) ),
+    }
+}
+
+/// Run every group in a data file, returning the number of cases passed and a description of each
+/// failure
+fn run_file( name:&str, data:&str ) -> ( usize, Vec< String > ) {
+    let groups:Value = serde_json::from_str( data ).unwrap( );
+    let mut passed = 0;
+    let mut failures = Vec::new( );
+    for ( group, tests ) in groups.as_object( ).unwrap( ) {
+        let vars:HashMap< String, TemplateValue > = tests["variables"].as_object( ).unwrap( ).iter( )
+            .filter_map( |( k, v )| to_value( v ).map( |v| ( k.clone( ), v ) ) )
+            .collect( );
+
+        for case in tests["testcases"].as_array( ).unwrap( ) {
+            let template = case[0].as_str( ).unwrap( );
+            // false marks a template which must fail to parse or to expand
+            let expected:Option< Vec< &str > > = match &case[1] {
+                Value::String( s ) => Some( vec![ s.as_str( ) ] ),
+                Value::Array( options ) => Some( options.iter( ).map( |o| o.as_str( ).unwrap( ) ).collect( ) ),
+                Value::Bool( false ) => None,
+                other => panic!( "{} {}: unexpected expectation {}", name, group, other ),
+            };
+            let expanded = UriTemplate::parse( template ).and_then( |parsed| parsed.expand_str( &vars ) );
+            match ( expanded, expected ) {
+                ( Ok( expanded ), Some( expected ) ) if expected.contains( &expanded.as_str( ) ) => passed += 1,
+                ( Ok( expanded ), expected ) => failures.push( format!( "{} {} `{}`: expanded to `{}`, expected {:?}", name, group, template, expanded, expected ) ),
+                ( Err( _ ), None ) => passed += 1,
+                ( Err( e ), Some( _ ) ) => failures.push( format!( "{} {} `{}`: failed with {:?}", name, group, template, e ) ),
+            }
+        }
+    }
+    ( passed, failures )
+}
+
+#[test]
+fn spec_examples( ) {
+    let ( passed, failures ) = run_file( "spec-examples", include_str!( "data/uritemplate-test/spec-examples.json" ) );
+    assert!( failures.is_empty( ), "{} passed, {} failed:\n{}", passed, failures.len( ), failures.join( "\n" ) );
+}
+
+#[test]
+fn spec_examples_by_section( ) {
+    let ( passed, failures ) = run_file( "spec-examples-by-section", include_str!( "data/uritemplate-test/spec-examples-by-section.json" ) );
+    assert!( failures.is_empty( ), "{} passed, {} failed:\n{}", passed, failures.len( ), failures.join( "\n" ) );
+}
+
+#[test]
+fn extended_tests( ) {
+    let ( passed, failures ) = run_file( "extended-tests", include_str!( "data/uritemplate-test/extended-tests.json" ) );
+    assert!( failures.is_empty( ), "{} passed, {} failed:\n{}", passed, failures.len( ), failures.join( "\n" ) );
+}
+
+#[test]
+fn negative_tests( ) {
+    let ( passed, failures ) = run_file( "negative-tests", include_str!( "data/uritemplate-test/negative-tests.json" ) );
+    assert!( failures.is_empty( ), "{} passed, {} failed:\n{}", passed, failures.len( ), failures.join( "\n" ) );
+}
+
+/// Templates which RFC 6570 section 2 does not allow, beyond those in negative-tests.json
+#[test]
+fn malformed_templates( ) {
+    let cases = [
+        "{}",
+        "{var,}",
+        "{var:}",
+        "{var:0}",
+        "{var:10000}",
+        "{var%2}",
+    ];
+    let accepted:Vec< ( &str, UriTemplate ) > = cases.iter( )
+        .filter_map( |t| UriTemplate::parse( t ).ok( ).map( |parsed| ( *t, parsed ) ) )
+        .collect( );
+    assert!( accepted.is_empty( ), "accepted malformed templates: {:?}", accepted.iter( ).map( |( t, _ )| t ).collect::< Vec< _ > >( ) );
+
+    assert_eq!( UriTemplate::parse( "{=path}" ).err( ), Some( TemplateError::ReservedOperator( 1 ) ) );
+}
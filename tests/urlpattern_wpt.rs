@@ -0,0 +1,246 @@
+//! Runs the web-platform-tests URLPattern conformance data, vendored from
+//! https://github.com/web-platform-tests/wpt/blob/master/urlpattern/resources/urlpatterntestdata.json
+#![cfg(feature = "urlpattern")]
+
+use base_url::urlpattern::{ ComponentResult, UrlPattern, UrlPatternInit, UrlPatternOptions, UrlPatternResult };
+use base_url::{ BaseUrl, TryFrom, Url };
+
+use serde_json::{ Map, Value };
+
+const COMPONENTS:[&str; 8] = [ "protocol", "username", "password", "hostname", "port", "pathname", "search", "hash" ];
+
+/// Cases which cannot be expressed through this crate's API, by their index in the data file. Each
+/// uses a base url which is not a BaseUrl, either `javascript:` or the empty string
+const UNSUPPORTED:&[usize] = &[ 202, 275, 276 ];
+
+enum Outcome {
+    Pass,
+    /// The case relies on something outside the scope of this crate's API, such as a base url which
+    /// cannot be a BaseUrl
+    Unsupported,
+    Fail( String ),
+}
+
+fn base_url( value:&str ) -> Option< BaseUrl > {
+    BaseUrl::try_from( value ).ok( )
+}
+
+fn to_init( object:&Map< String, Value > ) -> Option< UrlPatternInit > {
+    let field = |name:&str| object.get( name ).and_then( |v| v.as_str( ) ).map( |s| s.to_owned( ) );
+    let base = match object.get( "baseURL" ).and_then( |v| v.as_str( ) ) {
+        Some( b ) => Some( base_url( b )? ),
+        None => None,
+    };
+    Some( UrlPatternInit{
+        protocol: field( "protocol" ),
+        username: field( "username" ),
+        password: field( "password" ),
+        hostname: field( "hostname" ),
+        port: field( "port" ),
+        pathname: field( "pathname" ),
+        search: field( "search" ),
+        hash: field( "hash" ),
+        base_url: base,
+    } )
+}
+
+fn component< 'a >( pattern:&'a UrlPattern, name:&str ) -> &'a str {
+    match name {
+        "protocol" => pattern.protocol( ),
+        "username" => pattern.username( ),
+        "password" => pattern.password( ),
+        "hostname" => pattern.hostname( ),
+        "port" => pattern.port( ),
+        "pathname" => pattern.pathname( ),
+        "search" => pattern.search( ),
+        _ => pattern.hash( ),
+    }
+}
+
+fn result_component< 'a >( result:&'a UrlPatternResult, name:&str ) -> &'a ComponentResult {
+    match name {
+        "protocol" => &result.protocol,
+        "username" => &result.username,
+        "password" => &result.password,
+        "hostname" => &result.hostname,
+        "port" => &result.port,
+        "pathname" => &result.pathname,
+        "search" => &result.search,
+        _ => &result.hash,
+    }
+}
+
+/// The component of a base url as the pattern would inherit it
+fn base_component( base:&Url, name:&str ) -> String {
+    match name {
+        "protocol" => base.scheme( ).to_owned( ),
+        "hostname" => base.host_str( ).unwrap_or( "" ).to_owned( ),
+        "port" => base.port( ).map( |p| p.to_string( ) ).unwrap_or_default( ),
+        "pathname" => base.path( ).to_owned( ),
+        "search" => base.query( ).unwrap_or( "" ).to_owned( ),
+        "hash" => base.fragment( ).unwrap_or( "" ).to_owned( ),
+        _ => unreachable!( ),
+    }
+}
+
+fn run_case( case:&Value ) -> Outcome {
+    let case = case.as_object( ).unwrap( );
+    let expected_obj = case.get( "expected_obj" );
+    let expect_error = expected_obj.and_then( |v| v.as_str( ) ) == Some( "error" );
+    let empty:Vec< Value > = Vec::new( );
+    let exactly_empty:Vec< &str > = case.get( "exactly_empty_components" )
+                                        .and_then( |v| v.as_array( ) )
+                                        .unwrap_or( &empty )
+                                        .iter( )
+                                        .filter_map( |v| v.as_str( ) )
+                                        .collect( );
+
+    // Build the pattern
+    let args = case["pattern"].as_array( ).unwrap( );
+    let mut options = UrlPatternOptions::default( );
+    let mut input:Option< &Value > = None;
+    let mut base:Option< &str > = None;
+    for ( i, arg ) in args.iter( ).enumerate( ) {
+        if let Some( ignore_case ) = arg.get( "ignoreCase" ) {
+            options.ignore_case = ignore_case.as_bool( ).unwrap_or( false );
+        } else if i == 0 {
+            input = Some( arg );
+        } else if i == 1 && arg.is_string( ) {
+            base = arg.as_str( );
+        } else {
+            // Invalid argument combinations are a type error in the IDL binding, which a typed API
+            // rules out
+            return if expect_error { Outcome::Pass } else { Outcome::Unsupported };
+        }
+    }
+    let base = match base.map( base_url ) {
+        Some( None ) => return Outcome::Unsupported,
+        Some( Some( b ) ) => Some( b ),
+        None => None,
+    };
+
+    let init_object = input.and_then( |v| v.as_object( ) );
+    let built = match input {
+        Some( Value::String( s ) ) => UrlPattern::parse( s, base.as_ref( ), options ),
+        Some( Value::Object( object ) ) => {
+            if base.is_some( ) {
+                return if expect_error { Outcome::Pass } else { Outcome::Unsupported };
+            }
+            match to_init( object ) {
+                Some( init ) => UrlPattern::from_init( &init, options ),
+                None => return Outcome::Unsupported,
+            }
+        }
+        _ => UrlPattern::from_init( &UrlPatternInit::default( ), options ),
+    };
+    let pattern = match ( built, expect_error ) {
+        ( Err( _ ), true ) => return Outcome::Pass,
+        ( Ok( p ), true ) => return Outcome::Fail( format!( "expected an error, built {:?}", p ) ),
+        ( Err( e ), false ) => return Outcome::Fail( format!( "failed to build: {:?}", e ) ),
+        ( Ok( p ), false ) => p,
+    };
+
+    // Check the pattern strings
+    let pattern_base = init_object.and_then( |o| o.get( "baseURL" ) )
+                                  .and_then( |v| v.as_str( ) )
+                                  .or_else( || args.get( 1 ).and_then( |v| v.as_str( ) ) )
+                                  .map( |b| Url::parse( b ).unwrap( ) );
+    for ( index, name ) in COMPONENTS.iter( ).enumerate( ) {
+        let given = |n:&str| init_object.and_then( |o| o.get( n ) ).and_then( |v| v.as_str( ) );
+        let expected = match expected_obj.and_then( |o| o.get( name ) ).and_then( |v| v.as_str( ) ) {
+            Some( e ) => e.to_owned( ),
+            None if exactly_empty.contains( name ) => String::new( ),
+            None if given( name ).is_some( ) => given( name ).unwrap( ).to_owned( ),
+            None if *name != "username" && *name != "password"
+                && [ "protocol", "hostname", "port", "pathname", "search" ].iter( )
+                   .take_while( |n| COMPONENTS.iter( ).position( |c| c == *n ).unwrap( ) < index )
+                   .any( |n| given( n ).is_some( ) ) => "*".to_owned( ),
+            None => match &pattern_base {
+                Some( b ) if *name != "username" && *name != "password" => base_component( b, name ),
+                _ => "*".to_owned( ),
+            },
+        };
+        let actual = component( &pattern, name );
+        if actual != expected {
+            return Outcome::Fail( format!( "{} pattern is {:?}, expected {:?}", name, actual, expected ) );
+        }
+    }
+
+    // Match the input
+    let inputs = case.get( "inputs" ).and_then( |v| v.as_array( ) ).unwrap_or( &empty );
+    let expected_match = case.get( "expected_match" ).unwrap_or( &Value::Null );
+    let result = match ( inputs.first( ), inputs.get( 1 ) ) {
+        ( Some( Value::String( s ) ), base ) => {
+            let url = match base.and_then( |b| b.as_str( ) ) {
+                Some( b ) => Url::parse( b ).and_then( |b| b.join( s ) ),
+                None => Url::parse( s ),
+            };
+            url.ok( ).and_then( |u| pattern.exec( &u ) )
+        }
+        ( Some( Value::Object( object ) ), None ) => match to_init( object ) {
+            Some( init ) => pattern.exec_init( &init ),
+            None => return Outcome::Unsupported,
+        },
+        ( None, None ) => pattern.exec_init( &UrlPatternInit::default( ) ),
+        // An init along with a base url is a type error in the IDL binding
+        _ => return if expected_match == "error" { Outcome::Pass } else { Outcome::Unsupported },
+    };
+
+    let expected_match = match expected_match {
+        Value::Object( o ) => o,
+        _ => {
+            return match result {
+                None => Outcome::Pass,
+                Some( r ) => Outcome::Fail( format!( "expected no match, got {:?}", r ) ),
+            };
+        }
+    };
+    let result = match result {
+        Some( r ) => r,
+        None => return Outcome::Fail( "expected a match".to_owned( ) ),
+    };
+    for name in COMPONENTS.iter( ) {
+        let actual = result_component( &result, name );
+        let ( input, mut groups ):( String, Vec< ( String, Option< String > ) > ) = match expected_match.get( *name ) {
+            Some( c ) => (
+                c["input"].as_str( ).unwrap( ).to_owned( ),
+                c["groups"].as_object( ).unwrap( ).iter( )
+                           .map( |( k, v )| ( k.clone( ), v.as_str( ).map( |s| s.to_owned( ) ) ) )
+                           .collect( ),
+            ),
+            None if exactly_empty.contains( name ) => ( String::new( ), Vec::new( ) ),
+            None => ( String::new( ), vec![ ( "0".to_owned( ), Some( String::new( ) ) ) ] ),
+        };
+        let mut actual_groups = actual.groups.clone( );
+        groups.sort( );
+        actual_groups.sort( );
+        if actual.input != input || actual_groups != groups {
+            return Outcome::Fail( format!( "{} matched {:?} {:?}, expected {:?} {:?}",
+                                           name, actual.input, actual_groups, input, groups ) );
+        }
+    }
+    Outcome::Pass
+}
+
+#[test]
+fn wpt_urlpattern( ) {
+    let data:Value = serde_json::from_str( include_str!( "data/urlpatterntestdata.json" ) ).unwrap( );
+    let cases = data.as_array( ).unwrap( );
+
+    let mut failures = Vec::new( );
+    let mut unsupported = Vec::new( );
+    let mut passed = 0;
+    for ( index, case ) in cases.iter( ).enumerate( ) {
+        if case.get( "skip" ).is_some( ) {
+            continue;
+        }
+        match run_case( case ) {
+            Outcome::Pass => passed += 1,
+            Outcome::Unsupported => unsupported.push( index ),
+            Outcome::Fail( reason ) => failures.push( format!( "case {} {}: {}", index, case["pattern"], reason ) ),
+        }
+    }
+
+    assert_eq!( unsupported, UNSUPPORTED );
+    assert!( failures.is_empty( ), "{} passed, {} failed:\n{}", passed, failures.len( ), failures.join( "\n" ) );
+}